@@ -0,0 +1,41 @@
+//! State addresses of the os-cashier family, the same ones the processor computes
+//!
+//! ```
+//! use os_cashier::address;
+//!
+//! let alice = address::account("alice");
+//! assert_eq!(alice.len(), 70);
+//! assert!(alice.starts_with(&address::prefix()));
+//! ```
+
+pub const FAMILY_NAME: &str = "os-cashier";
+pub const TREASURY_NAME: &str = "os";
+pub const SETTINGS_NAMESPACE: &str = "000000";  // processor reads its configuration from sawtooth settings
+pub const BLOCK_INFO_NAMESPACE: &str = "00b10c";    // schedules, escrows and activity go by the block timestamps written here
+
+/// First 6 hex characters of every os-cashier address
+pub fn prefix() -> String {
+    hex::encode( openssl::sha::sha512(FAMILY_NAME.as_bytes() ))[0..6].to_string()
+}
+
+/// Address of the account `name`
+pub fn account(name: &str) -> String {
+    let name_hash = &hex::encode( openssl::sha::sha512(name.as_bytes()) )[64..];
+
+    prefix() + name_hash      // `String + &str` works fine !
+}
+
+/// Address of the accounts owned by `public_key`, must match OSCashierState::get_key_index_address in the processor
+pub fn key_index(public_key: &str) -> String {
+    account(&format!("key-index:{}", public_key))
+}
+
+/// Address of a sawtooth setting, same scheme as the settings family, see OSCashierState::get_setting_address in the processor
+pub fn setting(key: &str) -> String {
+    let mut parts: Vec<&str> = key.splitn(4, '.').collect();
+    parts.resize(4, "");
+
+    parts.iter().fold(SETTINGS_NAMESPACE.to_string(), |address, part| {
+        address + &hex::encode( openssl::sha::sha256(part.as_bytes()) )[..16]
+    })
+}
@@ -0,0 +1,566 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+use std::path;
+
+use protobuf::Message;
+use sawtooth_sdk::messages::batch::{Batch, BatchHeader, BatchList};
+use sawtooth_sdk::messages::transaction::{Transaction, TransactionHeader};
+use sawtooth_sdk::signing::{self, secp256k1::Secp256k1PublicKey};
+
+use os_cashier::payload::{OSCashierPayload, Actions};
+use os_cashier::proposal::Proposal;
+use os_cashier::{keys, util, BatchStatus, Client, ClientError, PendingBatch};
+use crate::output::{OutputFormat, Report};
+use serde_json::{json, Value};
+
+// Transactions collected instead of sent, see start_script()
+#[derive(Default)]
+struct Script {
+    transactions: Vec<Transaction>,
+    dependencies: Vec<String>,  // transaction ids the next transaction depends on
+}
+
+// The library's Client, with the command line's ways of sending transactions and printing results
+pub struct OSCashierClient {
+    pub api: Client,
+    wait: bool,     // wait for submitted batches to be committed
+    output: OutputFormat,
+    batch_file: Option<path::PathBuf>,  // write signed batches here instead of submitting them, see set_batch_file()
+    script: RefCell<Option<Script>>,
+}
+
+impl OSCashierClient {
+    /*
+     * `key` is a key name or path (see keys::resolve), None for the current OS user's key, like the sawtooth CLI
+     * `ephemeral` signs with a random key instead, that is lost when the command exits
+     */
+    pub fn new(rest_api_url: String, key: Option<&str>, ephemeral: bool, wait: bool, output: OutputFormat) -> Result<OSCashierClient, ClientError> {
+        let key_path = keys::resolve(key)?;
+        let builder = Client::builder(rest_api_url);
+        let builder = if ephemeral {
+            builder.key(keys::random()?)
+        } else if key_path.exists() || key.is_none() {
+            builder.key_file(key_path)  // no key file is fine until a command signs
+        } else {    // asked for a key that isn't there, most likely a typo
+            return Err(ClientError::Key(format!("{} doesn't exist", key_path.display())))
+        };
+
+        Ok(OSCashierClient {
+            api: builder.build()?,
+            wait,
+            output,
+            batch_file: None,
+            script: RefCell::new(None),
+        })
+    }
+
+    // Offline signing, transactions are written to `batch_file` as a serialized BatchList, to be posted later with submit()
+    pub fn set_batch_file(&mut self, batch_file: path::PathBuf) {
+        self.batch_file = Some(batch_file);
+    }
+
+    // Then nothing may be looked up on chain either, the commands take what they'd look up as arguments
+    pub fn is_offline(&self) -> bool {
+        self.batch_file.is_some()
+    }
+
+    // Whether a transaction kept by the script writes `address`, a command looking it up would see it as it was before the script
+    pub fn is_changed_by_script(&self, address: &str) -> Result<bool, ClientError> {
+        if let Some(script) = self.script.borrow().as_ref() {
+            for transaction in &script.transactions {
+                let header = TransactionHeader::parse_from_bytes(transaction.get_header())?;
+                if header.get_outputs().iter().any(|output| address.starts_with(output.as_str())) {
+                    return Ok(true)
+                }
+            }
+        }
+
+        Ok(false)
+    }
+
+    /*
+     * From here on, the transaction each command makes is kept instead of sent, finish_script() sends them all
+     *
+     * So a script's commands are built (and checked) one by one, but are applied all together, or not at all
+     */
+    pub fn start_script(&self) {
+        *self.script.borrow_mut() = Some(Script::default());
+    }
+
+    // The validator applies the next transaction only after these ones (by id) are committed, for scripts that aren't atomic
+    pub fn set_dependencies(&self, dependencies: Vec<String>) {
+        if let Some(script) = self.script.borrow_mut().as_mut() {
+            script.dependencies = dependencies;
+        }
+    }
+
+    // Ids of the transactions collected so far, in order
+    pub fn get_script_transaction_ids(&self) -> Vec<String> {
+        match self.script.borrow().as_ref() {
+            Some(script) => script.transactions.iter().map(|transaction| transaction.get_header_signature().to_string()).collect(),
+            None => vec![]
+        }
+    }
+
+    /*
+     * Sends the collected transactions, in one batch if `atomic`, else in a batch each, and reports each one's result
+     *
+     * `steps` are the (id, command) of each transaction, to label the results
+     */
+    pub fn finish_script(&self, steps: &[(String, String)], atomic: bool) -> Result<(), ClientError> {
+        let transactions = match self.script.borrow_mut().take() {
+            Some(script) => script.transactions,
+            None => vec![]
+        };
+        let transaction_ids: Vec<String> = transactions.iter().map(|transaction| transaction.get_header_signature().to_string()).collect();
+
+        let batches = if atomic {
+            vec![self.api.create_batch(transactions)?]
+        } else {
+            transactions.into_iter()
+                        .map(|transaction| self.api.create_batch(vec![transaction]))
+                        .collect::<Result<Vec<Batch>, ClientError>>()?
+        };
+        let batch_list_bytes = self.api.create_batchlist(batches).write_to_bytes()?;
+
+        // Signed or submitted only, there are no results yet
+        if self.batch_file.is_some() || !self.wait {
+            return self.send_batch_list(&batch_list_bytes)
+        }
+
+        let batch_ids = self.api.submit(&batch_list_bytes)?.batch_ids;
+        let statuses = self.api.poll_batch_statuses(&batch_ids)?;
+
+        let mut results = Vec::new();
+        for (i, transaction_id) in transaction_ids.iter().enumerate() {
+            let batch_id = if atomic { &batch_ids[0] } else { &batch_ids[i] };
+            let batch_status = statuses.iter().find(|status| status["id"] == batch_id.as_str()).cloned().unwrap_or(Value::Null);
+            let invalid = batch_status["invalid_transactions"].as_array()
+                                                              .and_then(|invalid| invalid.iter().find(|invalid| invalid["id"] == transaction_id.as_str()).cloned());
+
+            // In an invalid batch only one transaction is to blame, the others just weren't applied
+            let (status, message) = match (batch_status["status"].as_str().unwrap_or("UNKNOWN"), invalid) {
+                ("INVALID", Some(invalid)) => ("INVALID", invalid["message"].as_str().unwrap_or("no reason given").to_string()),
+                ("INVALID", None) => ("NOT_APPLIED", String::new()),
+                (status, _) => (if status == "COMMITTED" { "COMMITTED" } else { "PENDING" }, String::new())
+            };
+            results.push((steps.get(i).cloned().unwrap_or_default(), transaction_id.clone(), batch_id.clone(), status, message));
+        }
+
+        let committed = results.iter().all(|(_, _, _, status, _)| *status == "COMMITTED");
+        Report::new(json!({
+            "status": if committed { "COMMITTED" } else if results.iter().any(|result| result.3 == "INVALID") { "INVALID" } else { "PENDING" },
+            "batch_ids": batch_ids,
+            "transactions": results.iter().map(|((step, command), id, batch_id, status, message)| json!({
+                "step": step, "command": command, "id": id, "batch_id": batch_id, "status": status, "message": message
+            })).collect::<Vec<Value>>()
+        }))
+        .table(&["Step", "Command", "Transaction", "Status", "Message"], results.iter().map(|((step, command), id, _, status, message)| vec![
+            step.clone(), command.clone(), id.chars().take(16).collect(), status.to_string(), message.clone()
+        ]).collect())
+        .print(self.output);
+
+        match results.iter().find(|result| result.3 == "INVALID") {
+            Some((_, _, _, _, message)) => Err(ClientError::BatchInvalid(message.clone())),
+            None if !committed => Err(ClientError::BatchPending(self.api.batch_statuses_url(&batch_ids))),
+            None => Ok(())
+        }
+    }
+
+    // Every command ends here with its transaction, sent in a batch of its own, or kept for later in a script (see start_script)
+    pub fn send(&self, pending: PendingBatch) -> Result<(), ClientError> {
+        if let Some(script) = self.script.borrow_mut().as_mut() {
+            let dependencies = std::mem::take(&mut script.dependencies);
+            script.transactions.extend(pending.depends_on(&dependencies)?.into_transactions());
+            return Ok(())
+        }
+
+        self.send_batch_list(&pending.to_bytes()?)
+    }
+
+    fn send_batch_list(&self, batch_list_bytes: &[u8]) -> Result<(), ClientError> {
+        /* If this is a debug build, will write this data to a file too */
+        if cfg!(debug_assertions) {
+            use std::io::Write;
+
+            let batch_list = BatchList::parse_from_bytes(batch_list_bytes)?;
+            for batch in batch_list.get_batches() {
+                for transaction in batch.get_transactions() {
+                    let header = TransactionHeader::parse_from_bytes(transaction.get_header())?;
+                    eprintln!("TxnHeader: {:?}\n\nTransaction: {:?}\n\n", header, transaction);
+                }
+                let batch_header = BatchHeader::parse_from_bytes(batch.get_header())?;
+                eprintln!("BatchHeader: {:?}\n\nBatches: {:?}\n\n", batch_header, batch);
+            }
+            eprintln!("BatchList: {:?}\n\n", batch_list);
+
+            eprintln!("[DEBUG BUILD] Writing the bytes to os-cashier.tmp.batches");
+            match std::fs::File::create("os-cashier.tmp.batches").and_then(|mut file| file.write_all(batch_list_bytes)) {
+                Ok(_ok) => {},
+                Err(e) => { eprintln!("Error: {:?}", e) }
+            };
+        }
+
+        if let Some(batch_file) = &self.batch_file {
+            if let Err(e) = std::fs::write(batch_file, batch_list_bytes) {
+                return Err(ClientError::Validation(format!("Couldn't write {}: {}", batch_file.display(), e)))
+            }
+
+            let batch_ids = Client::get_batch_ids(batch_list_bytes)?;
+            Report::new(json!({ "status": "SIGNED", "batch_ids": batch_ids, "file": batch_file.display().to_string() }))
+                .field("Status", "SIGNED")
+                .field("File", batch_file.display())
+                .print(self.output);
+            return Ok(())
+        }
+
+        self.submit(batch_list_bytes)
+    }
+
+    // Posts batches, also ones signed elsewhere (see set_batch_file), and reports what became of them
+    pub fn submit(&self, batch_list_bytes: &[u8]) -> Result<(), ClientError> {
+        let submission = self.api.submit(batch_list_bytes)?;
+        let batch_ids = submission.batch_ids;
+
+        if !self.wait {
+            Report::new(json!({ "status": "SUBMITTED", "batch_ids": batch_ids, "link": submission.link }))
+                .field("Status", "SUBMITTED")
+                .field("Link", &submission.link)
+                .print(self.output);
+            return Ok(())
+        }
+
+        match self.api.wait_for_batches(&batch_ids)? {
+            BatchStatus::Committed => {
+                Report::new(json!({ "status": "COMMITTED", "batch_ids": batch_ids }))
+                    .field("Status", "COMMITTED")
+                    .print(self.output);
+                Ok(())
+            },
+            BatchStatus::Invalid(message) => Err(ClientError::BatchInvalid(message)),
+            BatchStatus::Pending => Err(ClientError::BatchPending(self.api.batch_statuses_url(&batch_ids)))
+        }
+    }
+
+    pub fn pending(&self, username: String) -> Result<(), ClientError> {
+        let account = self.api.get_existing_account(&username)?;
+
+        if account.signers.is_empty() {
+            return Err(ClientError::Validation(format!("User \"{}\" is not a multisig account", username)))
+        }
+
+        let proposals: Vec<(u64, String, usize)> = account.proposals.iter()
+            .map(|(id, proposal)| (*id, OSCashierClient::describe_proposal(proposal), proposal.get_approvals().len()))
+            .collect();
+
+        Report::new(json!({
+            "user": username,
+            "threshold": account.threshold,
+            "signers": account.signers,
+            "proposals": proposals.iter().map(|(id, operation, approvals)| json!({
+                "id": id, "operation": operation, "approvals": approvals
+            })).collect::<Vec<Value>>()
+        }))
+        .field("Signers", format!("{}-of-{} {:?}", account.threshold, account.signers.len(), account.signers))
+        .table(&["Id", "Operation", "Approvals"], proposals.iter().map(|(id, operation, approvals)| vec![
+            id.to_string(), operation.clone(), format!("{}/{}", approvals, account.threshold)
+        ]).collect())
+        .print(self.output);
+
+        Ok(())
+    }
+
+    fn describe_proposal(proposal: &Proposal) -> String {
+        let operation = proposal.get_operation();
+
+        match operation.get_action() {
+            Some(Actions::Transfer) => format!("Transfer {} to {}", operation.get_amount().abs(), operation.get_receiver()),
+            Some(Actions::SetSigners) => format!("SetSigners {}-of-{:?}", operation.get_threshold(), operation.get_keys()),
+            _ => format!("{} {}", operation.get_action_name(), operation.get_module_name())
+        }
+    }
+
+    // Decodes a batch file for review before it's submitted, checking every signature and payload hash, fails if one doesn't match
+    pub fn inspect(&self, batch_list_bytes: &[u8]) -> Result<(), ClientError> {
+        let batch_list = BatchList::parse_from_bytes(batch_list_bytes)?;
+        let context = match signing::create_context("secp256k1") {
+            Ok(context) => context,
+            Err(e) => return Err(ClientError::Key(format!("Couldn't create SECP256k1 context: {}", e)))
+        };
+        let verify = |signature: &str, message: &[u8], public_key: &str| -> bool {
+            Secp256k1PublicKey::from_hex(public_key)
+                .and_then(|public_key| context.verify(signature, message, &public_key))
+                .unwrap_or(false)
+        };
+
+        let mut all_valid = true;
+        let mut batches = Vec::new();
+        let mut rows = Vec::new();
+        for batch in batch_list.get_batches() {
+            let batch_header = BatchHeader::parse_from_bytes(batch.get_header())?;
+            let transaction_ids: Vec<&str> = batch.get_transactions().iter().map(|transaction| transaction.get_header_signature()).collect();
+            let batch_valid = verify(batch.get_header_signature(), batch.get_header(), batch_header.get_signer_public_key())
+                && batch_header.get_transaction_ids().iter().map(|id| id.as_str()).eq(transaction_ids.iter().copied());
+
+            let mut transactions = Vec::new();
+            for transaction in batch.get_transactions() {
+                let header = TransactionHeader::parse_from_bytes(transaction.get_header())?;
+                let payload = serde_cbor::from_slice::<OSCashierPayload>(transaction.get_payload()).ok();
+                let payload_valid = hex::encode(openssl::sha::sha512(transaction.get_payload()).to_vec()) == header.get_payload_sha512();
+                let valid = verify(transaction.get_header_signature(), transaction.get_header(), header.get_signer_public_key()) && payload_valid;
+                all_valid &= valid;
+
+                let payload_json = match &payload {
+                    Some(payload) => serde_json::to_value(payload).map_err(|e| ClientError::Serialization(e.to_string()))?,
+                    None => Value::Null
+                };
+                rows.push(vec![
+                    batch.get_header_signature().chars().take(16).collect(),
+                    transaction.get_header_signature().chars().take(16).collect(),
+                    payload_json["action"].as_str().unwrap_or("(not an os-cashier payload)").to_string(),
+                    header.get_signer_public_key().to_string(),
+                    (valid && batch_valid).to_string()
+                ]);
+                transactions.push(json!({
+                    "id": transaction.get_header_signature(),
+                    "signer": header.get_signer_public_key(),
+                    "family_name": header.get_family_name(),
+                    "family_version": header.get_family_version(),
+                    "nonce": header.get_nonce(),
+                    "inputs": header.get_inputs(),
+                    "outputs": header.get_outputs(),
+                    "dependencies": header.get_dependencies(),
+                    "signature_valid": valid,
+                    "payload_sha512_valid": payload_valid,
+                    "payload": payload_json
+                }));
+            }
+            all_valid &= batch_valid;
+
+            batches.push(json!({
+                "id": batch.get_header_signature(),
+                "signer": batch_header.get_signer_public_key(),
+                "signature_valid": batch_valid,
+                "transactions": transactions
+            }));
+        }
+
+        Report::new(json!({ "valid": all_valid, "batches": batches }))
+            .field("Batches", batch_list.get_batches().len())
+            .field("Valid", all_valid)
+            .table(&["Batch", "Transaction", "Action", "Signer", "Valid"], rows)
+            .print(self.output);
+
+        if !all_valid {
+            return Err(ClientError::Validation("The batch file has invalid signatures or payload hashes".to_string()))
+        }
+        Ok(())
+    }
+
+    pub fn whoami(&self) -> Result<(), ClientError> {
+        let public_key = self.api.get_public_key()?;
+        let accounts = self.api.get_owned_accounts(&public_key)?;
+
+        Report::new(json!({ "public_key": public_key, "accounts": accounts }))
+            .field("Public key", &public_key)
+            .table(&["Account"], accounts.iter().map(|name| vec![name.clone()]).collect())
+            .print(self.output);
+
+        Ok(())
+    }
+
+    pub fn name_offers(&self, username: String) -> Result<(), ClientError> {
+        let account = self.api.get_existing_account(&username)?;
+
+        Report::new(json!({
+            "user": username,
+            "listing": account.listing.as_ref().map(|listing| json!({ "price": listing.price, "payout": listing.payout })),
+            "offers": account.offers
+        }))
+        .field("Listing", match &account.listing {
+            Some(listing) => format!("{} (paid to {})", listing.price, listing.payout),
+            None => "not listed".to_string()
+        })
+        .table(&["Buyer", "Offer"], account.offers.iter().map(|(buyer, price)| vec![buyer.clone(), price.to_string()]).collect())
+        .print(self.output);
+
+        Ok(())
+    }
+
+    pub fn allowances(&self, username: String) -> Result<(), ClientError> {
+        let account = self.api.get_existing_account(&username)?;
+
+        Report::new(json!({ "user": username, "allowances": account.allowances }))
+            .table(&["Spender", "Allowance"], account.allowances.iter().map(|(spender, amount)| vec![spender.clone(), amount.to_string()]).collect())
+            .print(self.output);
+
+        Ok(())
+    }
+
+    pub fn schedules(&self, username: String) -> Result<(), ClientError> {
+        let account = self.api.get_existing_account(&username)?;
+
+        Report::new(json!({
+            "user": username,
+            "schedules": account.schedules.iter().map(|(id, schedule)| json!({
+                "id": id,
+                "receiver": schedule.get_receiver(),
+                "amount": schedule.get_amount(),
+                "interval": schedule.get_interval(),
+                "next_due": schedule.get_next_due(),
+                "runs_left": schedule.get_runs_left(),
+                "end_at": schedule.get_end_at()
+            })).collect::<Vec<Value>>()
+        }))
+        .table(&["Id", "Receiver", "Amount", "Every", "Next due", "Runs left", "Ends at"], account.schedules.iter().map(|(id, schedule)| vec![
+            id.to_string(),
+            schedule.get_receiver(),
+            schedule.get_amount().to_string(),
+            util::format_duration(schedule.get_interval()),
+            schedule.get_next_due().to_string(),
+            schedule.get_runs_left().map_or("-".to_string(), |runs| runs.to_string()),
+            schedule.get_end_at().map_or("-".to_string(), |end_at| end_at.to_string())
+        ]).collect())
+        .print(self.output);
+
+        Ok(())
+    }
+
+    pub fn escrows(&self, username: String) -> Result<(), ClientError> {
+        let account = self.api.get_existing_account(&username)?;
+
+        Report::new(json!({
+            "user": username,
+            "escrows": account.escrows.iter().map(|(id, escrow)| json!({
+                "id": id,
+                "beneficiary": escrow.get_beneficiary(),
+                "amount": escrow.get_amount(),
+                "arbiter": escrow.get_arbiter(),
+                "condition": escrow.get_condition().map(|condition| json!({
+                    "module": condition.get_module(), "min_seconds": condition.get_min_seconds()
+                })),
+                "deadline": escrow.get_deadline()
+            })).collect::<Vec<Value>>()
+        }))
+        .table(&["Id", "Beneficiary", "Amount", "Arbiter", "Condition", "Deadline"], account.escrows.iter().map(|(id, escrow)| vec![
+            id.to_string(),
+            escrow.get_beneficiary(),
+            escrow.get_amount().to_string(),
+            escrow.get_arbiter().map_or("-".to_string(), |arbiter| arbiter.clone()),
+            escrow.get_condition().map_or("-".to_string(), |condition| format!("{} plugged for {}", condition.get_module(), util::format_duration(condition.get_min_seconds()))),
+            escrow.get_deadline().to_string()
+        ]).collect())
+        .print(self.output);
+
+        Ok(())
+    }
+
+    pub fn members(&self, org: String) -> Result<(), ClientError> {
+        let account = self.api.get_existing_account(&org)?;
+
+        Report::new(json!({
+            "org": org,
+            "owner": account.key,
+            "members": account.members.iter().map(|(key, member)| json!({
+                "key": key, "role": member.role.to_lowercase(), "budget": member.budget
+            })).collect::<Vec<Value>>()
+        }))
+        .field("Owner", &account.key)
+        .table(&["Key", "Role", "Budget"], account.members.iter().map(|(key, member)| vec![
+            key.clone(), member.role.to_lowercase(), member.budget.to_string()
+        ]).collect())
+        .print(self.output);
+
+        Ok(())
+    }
+
+    pub fn claimable(&self, username: String) -> Result<(), ClientError> {
+        let account = self.api.get_existing_account(&username)?;
+
+        let claimable = self.api.claimable(&account, util::get_timestamp_sec());
+
+        Report::new(json!({ "user": username, "claimable": claimable }))
+            .table(&["Module", "Claimable CPUCoins"], claimable.iter().map(|(module_name, points)| vec![module_name.clone(), points.to_string()]).collect())
+            .print(self.output);
+
+        Ok(())
+    }
+
+    pub fn balance(&self, username: String) -> Result<(), ClientError> {
+        let points = self.api.get_existing_account(&username)?.points;
+
+        Report::new(json!({ "user": username, "points": points }))
+            .field("Points", points)
+            .print(self.output);
+
+        Ok(())
+    }
+
+    pub fn show(&self, username: String) -> Result<(), ClientError> {
+        let account = self.api.get_existing_account(&username)?;
+
+        let now = util::get_timestamp_sec();
+        let plugged_for: BTreeMap<&String, u64> = account.mods.iter()
+                                                          .map(|(module_name, plugged_at)| (module_name, now.saturating_sub(*plugged_at)))
+                                                          .collect();
+
+        let mut report = Report::new(json!({
+            "name": account.name,
+            "key": account.key,
+            "points": account.points,
+            "signers": account.signers,
+            "threshold": account.threshold,
+            "members": account.members.len(),
+            "mods": account.mods.iter().map(|(module_name, plugged_at)| json!({
+                "module": module_name, "plugged_at": plugged_at, "plugged_for": plugged_for[module_name]
+            })).collect::<Vec<Value>>()
+        }))
+        .field("Name", &account.name)
+        .field("Key", &account.key)
+        .field("Points", account.points);
+        if !account.signers.is_empty() {
+            report = report.field("Multisig", format!("{}-of-{}", account.threshold, account.signers.len()));
+        }
+        if !account.members.is_empty() {
+            report = report.field("Organisation members", account.members.len());
+        }
+
+        report.table(&["Module", "Plugged for"], plugged_for.iter().map(|(module_name, seconds)| vec![
+                  module_name.to_string(), util::format_duration(*seconds)
+              ]).collect())
+              .print(self.output);
+
+        Ok(())
+    }
+
+    pub fn users(&self) -> Result<(), ClientError> {
+        let accounts = self.api.get_accounts()?;
+
+        Report::new(json!({
+            "users": accounts.iter().map(|account| json!({
+                "name": account.name,
+                "points": account.points,
+                "mods": account.mods.keys().collect::<Vec<&String>>()
+            })).collect::<Vec<Value>>()
+        }))
+        .table(&["User", "Points", "Plugged modules"], accounts.iter().map(|account| vec![
+            account.name.clone(),
+            account.points.to_string(),
+            account.mods.keys().cloned().collect::<Vec<String>>().join(",")
+        ]).collect())
+        .print(self.output);
+
+        Ok(())
+    }
+
+    pub fn list_modules(&self) -> Result<(), ClientError> {
+        Report::new(json!({ "modules": self.api.modules() }))
+            .table(&["Module", "Performance Benefit"], self.api.modules().iter().map(|(module_name, rating)| vec![
+                module_name.clone(), rating.to_string()
+            ]).collect())
+            .print(self.output);
+
+        Ok(())
+    }
+}
@@ -1,29 +1,75 @@
+use std::cell::RefCell;
 use std::collections::BTreeMap;
 
-use std::{fs, path};
+use std::path;
 
 use crate::payload::OSCashierPayload;
 use protobuf::Message;
 use rand::{thread_rng, RngCore};
 use sawtooth_sdk::messages::batch::{Batch, BatchHeader, BatchList};
+use sawtooth_sdk::messages::setting::Setting;
 use sawtooth_sdk::messages::transaction::{Transaction, TransactionHeader};
-use sawtooth_sdk::signing::{
-    self, secp256k1::Secp256k1PrivateKey
-};
+use sawtooth_sdk::signing::{self, secp256k1::Secp256k1PrivateKey};
 
-use crate::payload::Actions;
+use crate::payload::{Actions, MAX_MEMO_LENGTH, MAX_RECIPIENTS};
+use crate::state::{AccountState, KeyIndex};
+use crate::address;
+use crate::util;
+use crate::pow;
+use crate::username;
+use crate::error::ClientError;
+use crate::keys;
+use serde_json::Value;
 
-const FAMILY_NAME: &str = "os-cashier";
 const FAMILY_VERSION: &str = "0.1";
+const COIN_MULTIPLIER: f32 = 0.05;  // same as in the processor
+const DEFAULT_POW_DIFFICULTY: u32 = 20;     // same as in the processor
+const BATCH_WAIT_SECONDS: u64 = 60;     // how long to wait for a batch to be committed, unless ClientBuilder::wait_timeout says otherwise
 
-pub struct OSCashierClient {
-    privatekey: Secp256k1PrivateKey, // read more on 'a
-    module_performance: BTreeMap<String, f32>,
+/// Final state of a submitted batch, see the REST API's /batch_statuses
+#[derive(Debug, Clone, PartialEq)]
+pub enum BatchStatus {
+    Committed,
+    Invalid(String),    // the processor's error message
+    Pending
+}
+
+/// What the REST API answered to posted batches
+#[derive(Debug, Clone)]
+pub struct Submission {
+    pub batch_ids: Vec<String>,
+    pub link: String,   // /batch_statuses of these batches
+}
+
+/// Configures a Client, see Client::builder
+pub struct ClientBuilder {
     rest_api_url: String,
+    privatekey: Option<Secp256k1PrivateKey>,
+    key_path: Option<path::PathBuf>,
+    wait_seconds: u64,
 }
 
-impl OSCashierClient {
-    pub fn new(rest_api_url: String) -> OSCashierClient {
+impl ClientBuilder {
+    /// Signs with this key
+    pub fn key(mut self, privatekey: Secp256k1PrivateKey) -> ClientBuilder {
+        self.privatekey = Some(privatekey);
+        self
+    }
+
+    /// Signs with the key in this file (see keys::load), read on the first signature, so an encrypted key asks for its passphrase only if needed
+    pub fn key_file<P: Into<path::PathBuf>>(mut self, key_path: P) -> ClientBuilder {
+        self.key_path = Some(key_path.into());
+        self
+    }
+
+    /// How long waiting for a batch goes on before it's reported as pending, 60 seconds by default
+    pub fn wait_timeout(mut self, seconds: u64) -> ClientBuilder {
+        self.wait_seconds = seconds;
+        self
+    }
+
+    /// Without a key, or if the key file doesn't exist, only the queries work and signing fails with ClientError::Key
+    pub fn build(self) -> Result<Client, ClientError> {
         let mut module_performance = BTreeMap::new();
 
         module_performance.insert("slab_allocator".to_string(), 0.4);
@@ -31,46 +77,78 @@ impl OSCashierClient {
         module_performance.insert("slob_allocator".to_string(), -0.5);
         module_performance.insert("buddy_allocator".to_string(), 0.2);
 
-        /*
-         * Getting keyfile as in https://github.com/hyperledger/sawtooth-sdk-python/blob/9ce6d0be599ea89c987da983ebe1c2beac14e6ee/examples/intkey_python/sawtooth_intkey/client_cli/intkey_cli.py#L315
-         */
-        let current_user = whoami::username();
-        let home_dir = match dirs::home_dir() {
-            Some(home_dir) => home_dir,
-            None => {
-                println!("Warning: Couldn't get the home directory path ! OS may not be supported... ");
-                println!("Warning: May use random keys for this run...");
-                path::PathBuf::new()
-            }
+        let public_key = match (&self.privatekey, &self.key_path) {
+            (Some(privatekey), _) => Some(keys::public_key(privatekey)?),
+            (None, Some(key_path)) if key_path.exists() => Some(keys::load_public_key(key_path)?),    // an encrypted key has its public key in the clear
+            _ => None
         };
 
-        let keys_dir = home_dir.join(".sawtooth").join("keys");
-        let keyfile = format!("{}/{}.priv", keys_dir.to_str().unwrap_or("."), current_user);
-
-        let privatekey: Secp256k1PrivateKey;
-        if std::path::Path::new(&keyfile).exists() {  // if available, will use keys generated by "sawtooth keygen"
-            privatekey = Secp256k1PrivateKey::from_hex(
-                fs::read_to_string(&keyfile)
-                    .expect("Something went wrong reading the file")
-                    .trim(),
-            )
-            .expect("Couldn't create PrivateKey object using contents of the .priv file");
-        } else {
-            privatekey = Secp256k1PrivateKey::from_hex(
-                &signing::create_context("secp256k1")
-                    .expect("ERROR: Couldn't create SECP256k1 context")
-                    .new_random_private_key()
-                    .expect("Error generating a random key")
-                    .as_hex(),
-            )
-            .expect("Couldn't create PrivateKey object from a random key");
+        Ok(Client {
+            rest_api_url: self.rest_api_url,
+            privatekey: RefCell::new(self.privatekey),
+            public_key,
+            key_path: self.key_path,
+            module_performance,
+            wait_seconds: self.wait_seconds,
+        })
+    }
+}
+
+/// Builds, signs and submits os-cashier transactions, and reads the family's state, through a validator's REST API
+///
+/// Every transaction method returns a PendingBatch, that is only sent with PendingBatch::submit
+pub struct Client {
+    privatekey: RefCell<Option<Secp256k1PrivateKey>>,   // loaded on the first signature, see sign_bytes()
+    public_key: Option<String>,     // None if there's no key, then only queries work
+    key_path: Option<path::PathBuf>,
+    module_performance: BTreeMap<String, f32>,
+    rest_api_url: String,
+    wait_seconds: u64,
+}
+
+impl Client {
+    /// `rest_api_url` is the validator's REST API, eg. "http://localhost:8008"
+    pub fn builder<S: Into<String>>(rest_api_url: S) -> ClientBuilder {
+        ClientBuilder {
+            rest_api_url: rest_api_url.into(),
+            privatekey: None,
+            key_path: None,
+            wait_seconds: BATCH_WAIT_SECONDS,
         }
+    }
 
-        OSCashierClient {
-            rest_api_url,
-            privatekey,
-            module_performance,
+    /// Modules that can be plugged, with their performance rating
+    pub fn modules(&self) -> &BTreeMap<String, f32> {
+        &self.module_performance
+    }
+
+    /// Points each plugged module of `account` would give if claimed at `now` (unix seconds)
+    ///
+    /// Estimate only, the processor uses its own clock when the claim actually executes
+    pub fn claimable(&self, account: &AccountState, now: u64) -> BTreeMap<String, f32> {
+        account.mods.iter().map(|(module_name, plugged_at)| {
+            let rating = self.module_performance.get(module_name).copied().unwrap_or(0.0);
+            let time_diff = now.saturating_sub(*plugged_at);
+
+            (module_name.clone(), COIN_MULTIPLIER * (time_diff as f32).sqrt() * rating)
+        }).collect()
+    }
+
+    /// A transaction of any `payload`, for actions without a method here, `addresses` are its inputs and outputs
+    ///
+    /// The block info namespace is added if missing, every action may read the block timestamp
+    pub fn transaction(&self, payload: OSCashierPayload, mut addresses: Vec<String>) -> Result<PendingBatch, ClientError> {
+        if !addresses.iter().any(|address| address == address::BLOCK_INFO_NAMESPACE) {
+            addresses.push(address::BLOCK_INFO_NAMESPACE.to_string());
         }
+        self.pending(self.create_transaction_for_addresses(payload.to_bytes(), addresses)?)
+    }
+
+    fn pending(&self, transaction: Transaction) -> Result<PendingBatch, ClientError> {
+        Ok(PendingBatch {
+            client: self,
+            transactions: vec![transaction],
+        })
     }
 
     /*
@@ -82,24 +160,40 @@ impl OSCashierClient {
         let signer = crypto_factory.new_signer(private_key.as_ref());
     */
 
-    fn create_transaction(&self, payload_bytes: Vec<u8>, asset_keys: Option<Vec<&str>>) -> Transaction {  // asset_key is used to get asset address
-        // Create Header -> Prerequisits: nonce, public key, inputs/outputs, payload_sha512hash
-        let nonce = hex::encode( OSCashierClient::get_nonce() );
+    fn create_transaction(&self, payload_bytes: Vec<u8>, asset_keys: Option<Vec<&str>>) -> Result<Transaction, ClientError> {  // asset_key is used to get asset address
+        let addresses = asset_keys.map(|keys| keys.iter().map(|asset_name| -> String { address::account(asset_name) }).collect());
 
-        let addresses = asset_keys.map(|keys| keys.iter().map(|asset_name| -> String { self.get_address(asset_name) }).collect());
+        self.create_transaction_for_addresses(
+            payload_bytes,
+            match addresses {
+                Some(mut addresses) => {
+                    addresses.push(address::BLOCK_INFO_NAMESPACE.to_string());
+                    addresses
+                },
+                None => vec![]
+            }
+        )
+    }
 
-        let inputs_vec = match addresses {
-            Some(addresses) => addresses,
-            None => vec![]
-        };
+    /*
+     * For when inputs/outputs aren't just accounts, eg. the settings namespace
+     *
+     * Every account written may read the block info (the processor tracks activity by block timestamp),
+     * so the addresses should include address::BLOCK_INFO_NAMESPACE, reading an undeclared address fails for good
+     */
+    fn create_transaction_for_addresses(&self, payload_bytes: Vec<u8>, addresses: Vec<String>) -> Result<Transaction, ClientError> {
+        // Create Header -> Prerequisits: nonce, public key, inputs/outputs, payload_sha512hash
+        let nonce = hex::encode( Client::get_nonce() );
+
+        let inputs_vec = addresses;
         let outputs_vec = inputs_vec.clone();
 
         let mut header = TransactionHeader::new();
-        header.set_family_name(FAMILY_NAME.to_string());
+        header.set_family_name(address::FAMILY_NAME.to_string());
         header.set_family_version(FAMILY_VERSION.to_string());
         header.set_nonce(nonce);
-        header.set_signer_public_key(self.get_public_key());
-        header.set_batcher_public_key(self.get_public_key());
+        header.set_signer_public_key(self.get_public_key()?);
+        header.set_batcher_public_key(self.get_public_key()?);
         header.set_inputs(protobuf::RepeatedField::from_vec(inputs_vec));
         header.set_outputs(protobuf::RepeatedField::from_vec(outputs_vec));
         header.set_payload_sha512( hex::encode( openssl::sha::sha512(&payload_bytes).to_vec() ) );
@@ -109,25 +203,19 @@ impl OSCashierClient {
          */
 
         // Create transaction -> Prerequisits: header_bytes, header_signature, payload_bytes
-        let header_bytes = header.write_to_bytes().expect("Error: Couldn't serialise TransactionHeader");
-        let header_signature = self.sign_bytes(&header_bytes);
+        let header_bytes = header.write_to_bytes()?;
+        let header_signature = self.sign_bytes(&header_bytes)?;
 
         let mut transaction = Transaction::new();
         transaction.set_header( header_bytes );
         transaction.set_header_signature( header_signature );
         transaction.set_payload( payload_bytes.to_vec() );
 
-        #[cfg(debug_assertions)] {
-            println!(
-                "TxnHeader: {:?}\n\nTransaction: {:?}\n\n",
-                header, transaction
-            );
-        }
-
-        transaction
+        Ok(transaction)
     }
 
-    fn create_batch(&self, transactions: Vec<Transaction>) -> Batch {
+    /// One batch of `transactions`, signed with this client's key, the validator applies them all or none
+    pub fn create_batch(&self, transactions: Vec<Transaction>) -> Result<Batch, ClientError> {
 
         /* From Docs ->
          * Once the TransactionHeader is constructed, its bytes are then used to create a signature.
@@ -140,51 +228,35 @@ impl OSCashierClient {
                                              .collect();
 
         let mut batch_header = BatchHeader::new();
-        batch_header.set_signer_public_key(self.get_public_key());
+        batch_header.set_signer_public_key(self.get_public_key()?);
         batch_header.set_transaction_ids( protobuf::RepeatedField::from_vec(transaction_ids) );
 
         // Creating Batch: Prereqs -> header_bytes, signature, transactions
-        let batch_header_bytes = batch_header.write_to_bytes().expect("Error: Couldn't serialize BatchHeader");
-        let batch_header_signature = self.sign_bytes(&batch_header_bytes);
+        let batch_header_bytes = batch_header.write_to_bytes()?;
+        let batch_header_signature = self.sign_bytes(&batch_header_bytes)?;
 
         let mut batch = Batch::new();
         batch.set_header(batch_header_bytes);
         batch.set_header_signature(batch_header_signature);
         batch.set_transactions( protobuf::RepeatedField::from_vec(transactions) );
 
-        #[cfg(debug_assertions)] {
-            println!(
-                "BatchHeader: {:?}\n\nBatches: {:?}\n\n",
-                batch_header, batch
-            );
-        }
-
-        batch
+        Ok(batch)
     }
 
-    fn create_batchlist(&self, batches: Vec<Batch>) -> BatchList {
+    /// The BatchList the REST API takes, serialize it with `write_to_bytes()`
+    pub fn create_batchlist(&self, batches: Vec<Batch>) -> BatchList {
         // Prereqs: batches
         let mut batch_list = BatchList::new();
         batch_list.set_batches( protobuf::RepeatedField::from_vec(batches) );
 
-        #[cfg(debug_assertions)] {
-            println!("BatchList: {:?}\n\n", batch_list);
-        }
-
         batch_list
     }
 
-    fn send_transaction(&self, batch_list_bytes: &[u8]) -> Result<String, reqwest::Error> {
-        /* If this is a debug build, will write this data to a file too */
-        if cfg!(debug_assertions) {
-            use std::io::Write;
-
-            println!("[DEBUG BUILD] Writing the bytes to os-cashier.tmp.batches");
-            let mut file = std::fs::File::create("os-cashier.tmp.batches").expect("Error creating file");
-            match file.write_all(batch_list_bytes) {
-                Ok(_ok) => {},
-                Err(e) => { println!("Error: {:?}", e) }
-            };
+    /// Posts a serialized BatchList, possibly signed elsewhere, it's only checked to decode, the validator checks the rest
+    pub fn submit(&self, batch_list_bytes: &[u8]) -> Result<Submission, ClientError> {
+        let batch_ids = Client::get_batch_ids(batch_list_bytes)?;
+        if batch_ids.is_empty() {
+            return Err(ClientError::Validation("The file holds no batches".to_string()))
         }
 
         // Using a blocking client... I don't know currently the async await in Rust, may change later
@@ -193,28 +265,179 @@ impl OSCashierClient {
             .post(format!("{}/batches", self.rest_api_url))
             .header("Content-Type", "application/octet-stream")
             .body(batch_list_bytes.to_vec()) // [LEARNT] - static lifetime was required, can also be simply fixed by passing a copy of the slice, as a vector
-            .send();
-        match response {
-            Ok(res) => match res.json::<serde_json::Value>() {
-                Ok(res_json) => {
-                    println!("{:#?}", res_json);
-                    //println!("{:#?}", res_str.replace("\\","").replace("\\n",""));
-                    Ok(res_json.to_string())
-                },
-                Err(e) => Err(e)
-            },
-            Err(e) => {
-                println!("Error: {:?}", e);
-                Err(e)
+            .send()?;
+        let res_json = Client::read_response(response)?;
+
+        Ok(Submission {
+            batch_ids,
+            link: res_json["link"].as_str().unwrap_or("").to_string(),
+        })
+    }
+
+    /// Batch ids are the batch header signatures
+    pub fn get_batch_ids(batch_list_bytes: &[u8]) -> Result<Vec<String>, ClientError> {
+        Ok(BatchList::parse_from_bytes(batch_list_bytes)?
+                     .get_batches()
+                     .iter()
+                     .map(|batch| batch.get_header_signature().to_string())
+                     .collect())
+    }
+
+    // Errors come back as {"error": {"code", "title", "message"}}, but a proxy in between may not answer JSON at all
+    fn read_response(response: reqwest::blocking::Response) -> Result<serde_json::Value, ClientError> {
+        let status = response.status();
+        let body = response.text()?;
+        let res_json = serde_json::from_str::<serde_json::Value>(&body);
+
+        if status.is_success() {
+            return res_json.map_err(|e| ClientError::Serialization(format!("Malformed REST API response: {}", e)))
+        }
+
+        let message = match res_json {
+            Ok(res_json) => res_json["error"]["message"].as_str().map(|message| message.to_string()),
+            Err(_) => None
+        };
+        Err(ClientError::RestApi {
+            status: status.as_u16(),
+            message: message.unwrap_or(body)
+        })
+    }
+
+    /// Waits until the batches are all committed, one is invalid, or the wait timeout passes
+    pub fn wait_for_batches(&self, batch_ids: &[String]) -> Result<BatchStatus, ClientError> {
+        let statuses = self.poll_batch_statuses(batch_ids)?;
+
+        if let Some(invalid) = statuses.iter().find(|status| status["status"] == "INVALID") {
+            let message = invalid["invalid_transactions"][0]["message"].as_str().unwrap_or("no reason given");
+            return Ok(BatchStatus::Invalid(message.to_string()))
+        }
+        if !statuses.is_empty() && statuses.iter().all(|status| status["status"] == "COMMITTED") {
+            return Ok(BatchStatus::Committed)
+        }
+        Ok(BatchStatus::Pending)
+    }
+
+    /// Like wait_for_batches, but returns the last statuses seen, as the REST API's /batch_statuses gives them
+    pub fn poll_batch_statuses(&self, batch_ids: &[String]) -> Result<Vec<Value>, ClientError> {
+        let client = reqwest::blocking::Client::new();
+        let deadline = util::get_timestamp_sec() + self.wait_seconds;
+
+        loop {
+            let remaining = deadline.saturating_sub(util::get_timestamp_sec()).max(1);
+            let res_json = Client::read_response(client
+                .get(format!("{}/batch_statuses?id={}&wait={}", self.rest_api_url, batch_ids.join(","), remaining))
+                .send()?)?;
+
+            let statuses = match res_json["data"].as_array() {
+                Some(statuses) => statuses.clone(),
+                None => vec![]
+            };
+
+            let finished = statuses.iter().any(|status| status["status"] == "INVALID")
+                || (!statuses.is_empty() && statuses.iter().all(|status| status["status"] == "COMMITTED"));
+            if finished || util::get_timestamp_sec() >= deadline {
+                return Ok(statuses)
             }
+
+            // UNKNOWN is answered right away, don't hammer the REST API
+            std::thread::sleep(std::time::Duration::from_secs(1));
         }
     }
 
-    fn get_address(&self, name: &str) -> String {
-        let prefix = &hex::encode( openssl::sha::sha512(FAMILY_NAME.as_bytes() ))[0..6];
-        let name_hash = &hex::encode( openssl::sha::sha512(name.as_bytes()) )[64..];
+    /// Where to check on batches later, eg. after BatchStatus::Pending
+    pub fn batch_statuses_url(&self, batch_ids: &[String]) -> String {
+        format!("{}/batch_statuses?id={}", self.rest_api_url, batch_ids.join(","))
+    }
+
+    /// Returns None if there is no entry at that address
+    pub fn get_state_entry(&self, address: &str) -> Result<Option<Vec<u8>>, ClientError> {
+        let client = reqwest::blocking::Client::new();
+        let response = client
+            .get(format!("{}/state/{}", self.rest_api_url, address))
+            .send()?;
 
-        prefix.to_string() + name_hash      // `String + &str` works fine !
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        let res_json = Client::read_response(response)?;
+        match res_json["data"].as_str() {
+            Some(data) => Ok(Some(base64::decode(data)?)),
+            None => Ok(None)
+        }
+    }
+
+    /// Every entry whose address starts with `address_prefix`, following the REST API's paging
+    pub fn get_state_entries(&self, address_prefix: &str) -> Result<Vec<(String, Vec<u8>)>, ClientError> {
+        let client = reqwest::blocking::Client::new();
+        let mut entries = Vec::new();
+        let mut next_url = Some(format!("{}/state?address={}", self.rest_api_url, address_prefix));
+
+        while let Some(url) = next_url {
+            let res_json = Client::read_response(client.get(&url).send()?)?;
+
+            if let Some(page) = res_json["data"].as_array() {
+                for entry in page {
+                    if let (Some(address), Some(data)) = (entry["address"].as_str(), entry["data"].as_str()) {
+                        entries.push((address.to_string(), base64::decode(data)?));
+                    }
+                }
+            }
+
+            next_url = res_json["paging"]["next"].as_str().map(|next| next.to_string());
+        }
+
+        Ok(entries)
+    }
+
+    /// Returns None if there is no such account
+    pub fn get_account_state(&self, username: &str) -> Result<Option<AccountState>, ClientError> {
+        match self.get_state_entry(&address::account(username))? {
+            Some(state_bytes) => Ok(Some(AccountState::from_bytes(&state_bytes)?)),
+            None => Ok(None)
+        }
+    }
+
+    /// For queries on one account, a missing account is the caller's mistake
+    pub fn get_existing_account(&self, username: &str) -> Result<AccountState, ClientError> {
+        match self.get_account_state(username)? {
+            Some(account) => Ok(account),
+            None => Err(ClientError::Validation(format!("User \"{}\" doesn't exist", username)))
+        }
+    }
+
+    /// All accounts, by name, entries that aren't accounts (eg. the key index) don't decode, and are skipped
+    pub fn get_accounts(&self) -> Result<Vec<AccountState>, ClientError> {
+        let entries = self.get_state_entries(&address::prefix())?;
+
+        let mut accounts: Vec<AccountState> = entries.iter()
+                                                     .filter_map(|(_, state_bytes)| AccountState::from_bytes(state_bytes).ok())
+                                                     .collect();
+        accounts.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(accounts)
+    }
+
+    /// A sawtooth setting, eg. "os_cashier.registration_mode"
+    pub fn get_setting(&self, key: &str) -> Result<Option<String>, ClientError> {
+        let setting_bytes = match self.get_state_entry(&address::setting(key))? {
+            Some(setting_bytes) => setting_bytes,
+            None => return Ok(None)
+        };
+        let setting = Setting::parse_from_bytes(&setting_bytes)?;
+
+        Ok(setting.get_entries()
+                  .iter()
+                  .find(|entry| entry.get_key() == key)
+                  .map(|entry| entry.get_value().to_string()))
+    }
+
+    /// Accounts owned by a public key, from its key index entry
+    pub fn get_owned_accounts(&self, public_key: &str) -> Result<Vec<String>, ClientError> {
+        match self.get_state_entry(&address::key_index(public_key))? {
+            Some(index_bytes) => Ok(KeyIndex::from_bytes(&index_bytes)?.accounts.into_iter().collect()),
+            None => Ok(Vec::new())
+        }
     }
 
     fn get_nonce() -> [u8; 16] {
@@ -224,99 +447,661 @@ impl OSCashierClient {
         nonce
     }
 
-    fn sign_bytes(&self, bytes: &[u8]) -> String {
-        let context =
-            signing::create_context("secp256k1").expect("Error Creating SECP256k1 Context");
+    /// Signature of `bytes` with this client's key, as hex
+    pub fn sign_bytes(&self, bytes: &[u8]) -> Result<String, ClientError> {
+        let context = match signing::create_context("secp256k1") {
+            Ok(context) => context,
+            Err(e) => return Err(ClientError::Key(format!("Couldn't create SECP256k1 context: {}", e)))
+        };
         let crypto_factory = signing::CryptoFactory::new(context.as_ref());
 
-        crypto_factory
-            .new_signer(&self.privatekey)
-            .sign(bytes)
-            .expect("FATAL ERROR: Couldn't Sign Message")
+        // Loaded here, not in build(), so that clients which don't sign never ask for the passphrase of an encrypted key
+        if self.privatekey.borrow().is_none() {
+            self.get_public_key()?;     // there's no key at all
+            if let Some(key_path) = &self.key_path {
+                *self.privatekey.borrow_mut() = Some(keys::load(key_path)?);
+            }
+        }
+
+        let privatekey = self.privatekey.borrow();
+        match privatekey.as_ref() {
+            Some(privatekey) => crypto_factory
+                .new_signer(privatekey)
+                .sign(bytes)
+                .map_err(|e| ClientError::Key(format!("Couldn't sign: {}", e))),
+            None => Err(self.missing_key())
+        }
+    }
+
+    /// Public key of the signing key, as hex
+    pub fn get_public_key(&self) -> Result<String, ClientError> {
+        self.public_key.clone().ok_or_else(|| self.missing_key())
     }
 
-    fn get_public_key(&self) -> String {
-        let context =
-            signing::create_context("secp256k1").expect("Error Creating SECP256k1 Context");
-        let crypto_factory = signing::CryptoFactory::new(context.as_ref());
+    fn missing_key(&self) -> ClientError {
+        match &self.key_path {
+            Some(key_path) => ClientError::Key(format!("No key at {}", key_path.display())),
+            None => ClientError::Key("No key to sign with, see ClientBuilder::key".to_string())
+        }
+    }
+
+    /// `sponsor` vouches for (or pays for) the registration, `owner_key` registers the name for another key
+    ///
+    /// Without a sponsor, the registration settings are read to know whether a proof-of-work puzzle has to be solved,
+    /// unless `pow_difficulty` is given (0 for no puzzle), solving it may take a while, see get_pow_difficulty
+    pub fn reg(&self, username: String, sponsor: Option<String>, owner_key: Option<String>, pow_difficulty: Option<u32>) -> Result<PendingBatch, ClientError> {
+        // Checked before signing, the processor would reject it anyway
+        username::validate(&username).map_err(ClientError::Validation)?;
+
+        let owner_key = match owner_key {
+            Some(owner_key) => owner_key,
+            None => self.get_public_key()?
+        };
+
+        let mut payload = OSCashierPayload::new(Actions::Register, username.clone());
+        payload.set_public_key(owner_key.clone());
+
+        let mut addresses = vec![
+            address::account(&username),
+            address::key_index(&owner_key),
+            address::SETTINGS_NAMESPACE.to_string(),
+            address::BLOCK_INFO_NAMESPACE.to_string()
+        ];
+
+        match sponsor {
+            Some(sponsor) => {
+                addresses.push(address::account(&sponsor));
+                addresses.push(address::account(address::TREASURY_NAME));    // in case of a fee
+                payload.set_sponsor(sponsor);
+            },
+            None => {
+                let difficulty = match pow_difficulty {
+                    Some(difficulty) => difficulty,
+                    None => self.get_pow_difficulty()?
+                };
+
+                if difficulty > 0 {
+                    payload.set_pow_nonce(pow::solve(&username, &owner_key, difficulty));
+                }
+            }
+        };
+
+        let payload_bytes = payload.to_bytes();
 
-        crypto_factory
-            .new_signer(&self.privatekey)
-            .get_public_key()
-            .expect("FATAL ERROR: Couldn't get Public Key")
-            .as_hex()
+        self.pending(self.create_transaction_for_addresses(payload_bytes, addresses)?)
     }
 
-    pub fn reg(&self, username: String) {
-        let payload_bytes = OSCashierPayload::new(Actions::Register, username.clone()).to_bytes();
+    /// Bits of proof-of-work an unsponsored registration needs, from the chain's settings, 0 if registration is open (or pow isn't a mode)
+    pub fn get_pow_difficulty(&self) -> Result<u32, ClientError> {
+        let modes = self.get_setting("os_cashier.registration_mode")?.unwrap_or_else(|| "open".to_string());
+        let modes: Vec<&str> = modes.split(',').map(|mode| mode.trim()).collect();
 
-        let transaction = self.create_transaction(payload_bytes, Some(vec![&username]));
-        let batch       = self.create_batch(vec![transaction]);
-        let batch_list  = self.create_batchlist(vec![batch]);
+        if modes.contains(&"open") || !modes.contains(&"pow") {
+            return Ok(0)
+        }
 
-        let batch_list_bytes = batch_list
-            .write_to_bytes()
-            .expect("Error: Couldn't serialise batch list");
+        Ok(self.get_setting("os_cashier.pow_difficulty")?
+               .and_then(|difficulty| difficulty.trim().parse().ok())
+               .unwrap_or(DEFAULT_POW_DIFFICULTY))
+    }
 
-        self.send_transaction(&batch_list_bytes).expect("Error: Couldn't send the Register request");
+    // The processor rejects longer memos
+    fn check_memo(memo: &Option<String>) -> Result<(), ClientError> {
+        match memo {
+            Some(memo) if memo.len() > MAX_MEMO_LENGTH => Err(ClientError::Validation(format!("Memo can be at most {} bytes", MAX_MEMO_LENGTH))),
+            _ => Ok(())
+        }
     }
 
-    pub fn plug(&self, username: String, module_name: String) {
+    /// Plugs `module_name` into the account of `username`, points are earned from then on
+    ///
+    /// ```
+    /// # use os_cashier::{keys, Client};
+    /// let client = Client::builder("http://localhost:8008").key(keys::random()?).build()?;
+    ///
+    /// let pending = client.plug("alice".to_string(), "slab_allocator".to_string())?;
+    /// assert_eq!(pending.transaction_ids().len(), 1);
+    /// # Ok::<(), os_cashier::ClientError>(())
+    /// ```
+    pub fn plug(&self, username: String, module_name: String) -> Result<PendingBatch, ClientError> {
         let mut payload = OSCashierPayload::new(Actions::PlugMod, username.clone());
         payload.set_module(module_name);
 
         let payload_bytes = payload.to_bytes();
 
-        self.send_transaction(
-            &self.create_batchlist(
-                vec![self.create_batch(
-                    vec![self.create_transaction(payload_bytes, Some(vec![&username]))]
-                )]
-            )
-            .write_to_bytes()
-            .expect("Error: Couldn't serialise batchlist")
-        ).expect("Error: Couldn't send the Register request");
+        self.pending(self.create_transaction_for_addresses(payload_bytes, Client::plugging_addresses(&username))?)
     }
 
-    pub fn unplug(&self, username: String, module_name: String) {
+    pub fn unplug(&self, username: String, module_name: String) -> Result<PendingBatch, ClientError> {
         let mut payload = OSCashierPayload::new(Actions::UnplugMod, username.clone());
         payload.set_module(module_name);
 
         let payload_bytes = payload.to_bytes();
 
-        self.send_transaction(
-            &self.create_batchlist(
-                vec![self.create_batch(
-                    vec![self.create_transaction(payload_bytes, Some(vec![&username]))]
-                )]
-            )
-            .write_to_bytes()
-            .expect("Error: Couldn't serialise batchlist")
-        ).expect("Error: Couldn't send the Register request");
+        self.pending(self.create_transaction(payload_bytes, Some(vec![&username]))?)
     }
 
-    pub fn transfer(&self, sender: String, receiver: String, amount: f32) {
+    /// Reusing `idempotency_key` when retrying makes sure the transfer happens only once
+    pub fn transfer(&self, sender: String, receiver: String, amount: f32, memo: Option<String>, idempotency_key: Option<String>) -> Result<PendingBatch, ClientError> {
+        Client::check_memo(&memo)?;
+
+        let addresses = Client::transfer_addresses(vec![&sender, &receiver], &idempotency_key);
+
         let mut payload = OSCashierPayload::new(Actions::Transfer, sender.clone());
         payload.set_receiver(receiver.clone());
         payload.set_amount(amount);
+        payload.set_memo(memo.unwrap_or_default());
+        payload.set_idempotency_key(idempotency_key.unwrap_or_default());
+
+        let payload_bytes = payload.to_bytes();
+
+        self.pending(self.create_transaction_for_addresses(payload_bytes, addresses)?)
+    }
+
+    /// 1 to MAX_RECIPIENTS receivers, all paid or none
+    pub fn multi_transfer(&self, sender: String, recipients: BTreeMap<String,f32>, memo: Option<String>, idempotency_key: Option<String>) -> Result<PendingBatch, ClientError> {
+        if recipients.is_empty() || recipients.len() > MAX_RECIPIENTS {
+            return Err(ClientError::Validation(format!("A multi transfer needs 1 to {} receivers, got {}", MAX_RECIPIENTS, recipients.len())))
+        }
+        Client::check_memo(&memo)?;
+
+        let mut asset_keys = vec![sender.as_str()];
+        asset_keys.extend(recipients.keys().map(|receiver| receiver.as_str()));
+        let addresses = Client::transfer_addresses(asset_keys, &idempotency_key);
+
+        let mut payload = OSCashierPayload::new(Actions::MultiTransfer, sender.clone());
+        payload.set_recipients(recipients.clone());
+        payload.set_memo(memo.unwrap_or_default());
+        payload.set_idempotency_key(idempotency_key.unwrap_or_default());
+
+        let payload_bytes = payload.to_bytes();
+
+        self.pending(self.create_transaction_for_addresses(payload_bytes, addresses)?)
+    }
+
+    // An idempotency key is kept for the os_cashier.idempotency_window setting, so it reads the settings too
+    fn transfer_addresses(asset_keys: Vec<&str>, idempotency_key: &Option<String>) -> Vec<String> {
+        let mut addresses: Vec<String> = asset_keys.iter().map(|asset_name| address::account(asset_name)).collect();
+        addresses.push(address::BLOCK_INFO_NAMESPACE.to_string());
+        if idempotency_key.is_some() {
+            addresses.push(address::SETTINGS_NAMESPACE.to_string());
+        }
+        addresses
+    }
+
+    pub fn claim(&self, username: String, module_name: String) -> Result<PendingBatch, ClientError> {
+        let mut payload = OSCashierPayload::new(Actions::Claim, username.clone());
+        payload.set_module(module_name);
+
+        let payload_bytes = payload.to_bytes();
+
+        self.pending(self.create_transaction(payload_bytes, Some(vec![&username]))?)
+    }
+
+    /// Settles all plugged modules in one transaction, then plugs `module_names` (may be empty)
+    pub fn reboot(&self, username: String, module_names: Vec<String>) -> Result<PendingBatch, ClientError> {
+        let mut payload = OSCashierPayload::new(Actions::Reboot, username.clone());
+        payload.set_modules(module_names);
+
+        let payload_bytes = payload.to_bytes();
+
+        self.pending(self.create_transaction_for_addresses(payload_bytes, Client::plugging_addresses(&username))?)
+    }
+
+    // Escrow conditions count from the block a module was plugged in, so plugging reads the block info too
+    fn plugging_addresses(username: &str) -> Vec<String> {
+        vec![address::account(username), address::BLOCK_INFO_NAMESPACE.to_string()]
+    }
+
+    /// Has to be signed by the current key, or by the recovery key (which then waits for the timelock)
+    ///
+    /// `old_key` is the account's current key, read from the chain if None
+    pub fn rotate_key(&self, username: String, new_key: String, old_key: Option<String>) -> Result<PendingBatch, ClientError> {
+        // When signing with the recovery key, the old key is the account's, not ours
+        let old_key = match old_key {
+            Some(old_key) => old_key,
+            None => match self.get_account_state(&username)? {
+                Some(account) => account.key,
+                None => self.get_public_key()?
+            }
+        };
+        let addresses = vec![
+            address::account(&username),
+            address::key_index(&old_key),
+            address::key_index(&new_key),
+            address::BLOCK_INFO_NAMESPACE.to_string()    // the recovery timelock goes by the block timestamp
+        ];
+
+        let mut payload = OSCashierPayload::new(Actions::RotateKey, username.clone());
+        payload.set_public_key(new_key);
+
+        let payload_bytes = payload.to_bytes();
+
+        self.pending(self.create_transaction_for_addresses(payload_bytes, addresses)?)
+    }
+
+    pub fn set_recovery(&self, username: String, recovery_key: String, timelock: u64) -> Result<PendingBatch, ClientError> {
+        let mut payload = OSCashierPayload::new(Actions::SetRecovery, username.clone());
+        payload.set_public_key(recovery_key);
+        payload.set_timelock(timelock);
+
+        let payload_bytes = payload.to_bytes();
+
+        self.pending(self.create_transaction(payload_bytes, Some(vec![&username]))?)
+    }
+
+    /// Empty `keys` makes it a single key account again
+    pub fn set_signers(&self, username: String, keys: Vec<String>, threshold: u32) -> Result<PendingBatch, ClientError> {
+        let mut payload = OSCashierPayload::new(Actions::SetSigners, username.clone());
+        payload.set_signers(keys, threshold);
+
+        let payload_bytes = payload.to_bytes();
+
+        self.pending(self.create_transaction(payload_bytes, Some(vec![&username]))?)
+    }
+
+    /// `operation` is a normal payload on `username`, it runs once enough signers approve it
+    pub fn propose(&self, username: String, operation: OSCashierPayload) -> Result<PendingBatch, ClientError> {
+        let receiver = operation.get_receiver();
+
+        let mut payload = OSCashierPayload::new(Actions::Propose, username.clone());
+        payload.set_operation(operation);
+
+        let payload_bytes = payload.to_bytes();
+
+        let mut asset_keys = vec![username.as_str()];
+        if !receiver.is_empty() {
+            asset_keys.push(&receiver);
+        }
+
+        self.pending(self.create_transaction(payload_bytes, Some(asset_keys))?)
+    }
+
+    /// `receiver` is the one of the proposed transfer ("" if it isn't a transfer), read from the proposal if None
+    pub fn approve(&self, username: String, proposal_id: u64, receiver: Option<String>) -> Result<PendingBatch, ClientError> {
+        // The approval may execute a transfer, so the receiver's address has to be in inputs/outputs too
+        let receiver = match receiver {
+            Some(receiver) => receiver,
+            None => self.get_account_state(&username)?
+                        .and_then(|account| account.proposals.get(&proposal_id).map(|p| p.get_operation().get_receiver()))
+                        .unwrap_or_default()
+        };
+
+        let mut payload = OSCashierPayload::new(Actions::ApproveProposal, username.clone());
+        payload.set_proposal_id(proposal_id);
+
+        let payload_bytes = payload.to_bytes();
+
+        // A proposed plug reads the block info, like plug()
+        let mut addresses = Client::plugging_addresses(&username);
+        if !receiver.is_empty() {
+            addresses.push(address::account(&receiver));
+        }
+
+        self.pending(self.create_transaction_for_addresses(payload_bytes, addresses)?)
+    }
+
+    /// Balance goes to `sweep_to`, or to the treasury if None
+    pub fn deregister(&self, username: String, sweep_to: Option<String>) -> Result<PendingBatch, ClientError> {
+        let mut payload = OSCashierPayload::new(Actions::Deregister, username.clone());
+        let sweep_to = sweep_to.unwrap_or_else(|| address::TREASURY_NAME.to_string());
+        if sweep_to != address::TREASURY_NAME {
+            payload.set_receiver(sweep_to.clone());
+        }
+
+        let payload_bytes = payload.to_bytes();
+
+        let addresses = vec![
+            address::account(&username),
+            address::account(&sweep_to),
+            address::key_index(&self.get_public_key()?),
+            address::BLOCK_INFO_NAMESPACE.to_string()
+        ];
+
+        self.pending(self.create_transaction_for_addresses(payload_bytes, addresses)?)
+    }
+
+    /// Needs the loaded key to be in the os_cashier.admin_keys setting, `owner_key` is read from the chain if None
+    pub fn reap(&self, username: String, owner_key: Option<String>) -> Result<PendingBatch, ClientError> {
+        let payload_bytes = OSCashierPayload::new(Actions::Reap, username.clone()).to_bytes();
+
+        let owner_key = match owner_key {
+            Some(owner_key) => owner_key,
+            None => match self.get_account_state(&username)? {
+                Some(account) => account.key,
+                None => String::new()
+            }
+        };
+        let addresses = vec![
+            address::account(&username),
+            address::account(address::TREASURY_NAME),
+            address::key_index(&owner_key),
+            address::SETTINGS_NAMESPACE.to_string(),
+            address::BLOCK_INFO_NAMESPACE.to_string()    // dormancy goes by the block timestamp
+        ];
+
+        self.pending(self.create_transaction_for_addresses(payload_bytes, addresses)?)
+    }
+
+    pub fn transfer_name(&self, username: String, new_key: String) -> Result<PendingBatch, ClientError> {
+        let addresses = vec![
+            address::account(&username),
+            address::key_index(&self.get_public_key()?),
+            address::key_index(&new_key),
+            address::BLOCK_INFO_NAMESPACE.to_string()
+        ];
+
+        let mut payload = OSCashierPayload::new(Actions::TransferName, username);
+        payload.set_public_key(new_key);
+
+        let payload_bytes = payload.to_bytes();
+
+        self.pending(self.create_transaction_for_addresses(payload_bytes, addresses)?)
+    }
+
+    /// A price of 0 takes the listing down
+    pub fn list_name(&self, username: String, price: f32, payout: String) -> Result<PendingBatch, ClientError> {
+        let mut payload = OSCashierPayload::new(Actions::ListName, username.clone());
+        payload.set_amount(price);
+        payload.set_receiver(payout.clone());
+
+        let payload_bytes = payload.to_bytes();
+
+        self.pending(self.create_transaction(payload_bytes, Some(vec![&username, &payout]))?)
+    }
+
+    /// `listing` is the seller's key and the payout account of the listing, read from the chain if None
+    pub fn buy_name(&self, username: String, buyer: String, max_price: f32, listing: Option<(String, String)>) -> Result<PendingBatch, ClientError> {
+        if !max_price.is_finite() {
+            return Err(ClientError::Validation(format!("Invalid max price: {}", max_price)))
+        }
+
+        let (seller_key, payout) = match listing {
+            Some(listing) => listing,
+            None => match self.get_existing_account(&username)? {
+                AccountState { key, listing: Some(listing), .. } => (key, listing.payout),
+                _ => return Err(ClientError::Validation(format!("\"{}\" is not for sale", username)))
+            }
+        };
+
+        let addresses = vec![
+            address::account(&username),
+            address::account(&buyer),
+            address::account(&payout),
+            address::key_index(&seller_key),
+            address::key_index(&self.get_public_key()?),
+            address::BLOCK_INFO_NAMESPACE.to_string()
+        ];
+
+        let mut payload = OSCashierPayload::new(Actions::BuyName, username);
+        payload.set_sponsor(buyer);
+        payload.set_amount(max_price);
+
+        let payload_bytes = payload.to_bytes();
+
+        self.pending(self.create_transaction_for_addresses(payload_bytes, addresses)?)
+    }
+
+    /// A price of 0 withdraws the offer
+    pub fn offer_name(&self, username: String, buyer: String, price: f32) -> Result<PendingBatch, ClientError> {
+        let mut payload = OSCashierPayload::new(Actions::OfferName, username.clone());
+        payload.set_sponsor(buyer.clone());
+        payload.set_amount(price);
+
+        let payload_bytes = payload.to_bytes();
+
+        self.pending(self.create_transaction(payload_bytes, Some(vec![&username, &buyer]))?)
+    }
+
+    /// `buyer_key` is read from the chain if None
+    pub fn accept_offer(&self, username: String, buyer: String, payout: String, buyer_key: Option<String>) -> Result<PendingBatch, ClientError> {
+        let buyer_key = match buyer_key {
+            Some(buyer_key) => buyer_key,
+            None => self.get_existing_account(&buyer)?.key
+        };
+
+        let addresses = vec![
+            address::account(&username),
+            address::account(&buyer),
+            address::account(&payout),
+            address::key_index(&self.get_public_key()?),
+            address::key_index(&buyer_key),
+            address::BLOCK_INFO_NAMESPACE.to_string()
+        ];
+
+        let mut payload = OSCashierPayload::new(Actions::AcceptOffer, username);
+        payload.set_sponsor(buyer);
+        payload.set_receiver(payout);
+
+        let payload_bytes = payload.to_bytes();
+
+        self.pending(self.create_transaction_for_addresses(payload_bytes, addresses)?)
+    }
+
+    /// Sets the allowance, replacing any earlier one
+    pub fn approve_allowance(&self, username: String, spender: String, amount: f32) -> Result<PendingBatch, ClientError> {
+        let mut payload = OSCashierPayload::new(Actions::Approve, username.clone());
+        payload.set_spender(spender.clone());
+        payload.set_amount(amount);
+
+        let payload_bytes = payload.to_bytes();
+
+        self.pending(self.create_transaction(payload_bytes, Some(vec![&username, &spender]))?)
+    }
+
+    pub fn revoke_allowance(&self, username: String, spender: String) -> Result<PendingBatch, ClientError> {
+        let mut payload = OSCashierPayload::new(Actions::Revoke, username.clone());
+        payload.set_spender(spender);
+
+        let payload_bytes = payload.to_bytes();
+
+        self.pending(self.create_transaction(payload_bytes, Some(vec![&username]))?)
+    }
+
+    /// Signed by the key of `spender`
+    pub fn transfer_from(&self, owner: String, spender: String, receiver: String, amount: f32) -> Result<PendingBatch, ClientError> {
+        let mut payload = OSCashierPayload::new(Actions::TransferFrom, owner.clone());
+        payload.set_spender(spender.clone());
+        payload.set_receiver(receiver.clone());
+        payload.set_amount(amount);
+
+        let payload_bytes = payload.to_bytes();
+
+        self.pending(self.create_transaction(payload_bytes, Some(vec![&owner, &spender, &receiver]))?)
+    }
+
+    /// `runs` of 0 and `end_at` of 0 mean no limit
+    pub fn create_schedule(&self, username: String, receiver: String, amount: f32, interval: u64, runs: u32, end_at: u64) -> Result<PendingBatch, ClientError> {
+        let mut payload = OSCashierPayload::new(Actions::CreateSchedule, username.clone());
+        payload.set_receiver(receiver.clone());
+        payload.set_amount(amount);
+        payload.set_schedule(interval, runs, end_at);
+
+        let addresses = vec![
+            address::account(&username),
+            address::account(&receiver),
+            address::BLOCK_INFO_NAMESPACE.to_string()
+        ];
+
+        let payload_bytes = payload.to_bytes();
+
+        self.pending(self.create_transaction_for_addresses(payload_bytes, addresses)?)
+    }
+
+    pub fn cancel_schedule(&self, username: String, schedule_id: u64) -> Result<PendingBatch, ClientError> {
+        let mut payload = OSCashierPayload::new(Actions::CancelSchedule, username.clone());
+        payload.set_schedule_id(schedule_id);
+
+        let payload_bytes = payload.to_bytes();
+
+        self.pending(self.create_transaction(payload_bytes, Some(vec![&username]))?)
+    }
+
+    /// Any key can run due schedules, the receivers are read from the payer's current schedules if None
+    pub fn execute_due(&self, username: String, receivers: Option<Vec<String>>) -> Result<PendingBatch, ClientError> {
+        let receivers = match receivers {
+            Some(receivers) => receivers,
+            None => self.get_existing_account(&username)?.schedules.values().map(|schedule| schedule.get_receiver()).collect()
+        };
+
+        let mut addresses = vec![address::account(&username), address::BLOCK_INFO_NAMESPACE.to_string()];
+        for receiver in receivers {
+            let receiver_address = address::account(&receiver);
+            if !addresses.contains(&receiver_address) {
+                addresses.push(receiver_address);
+            }
+        }
+
+        let payload = OSCashierPayload::new(Actions::ExecuteDue, username);
+        let payload_bytes = payload.to_bytes();
+
+        self.pending(self.create_transaction_for_addresses(payload_bytes, addresses)?)
+    }
+
+    /// `arbiter` and `module` may be empty, but not both
+    pub fn create_escrow(&self, username: String, beneficiary: String, amount: f32, arbiter: String, module: String, min_plugged: u64, deadline: u64) -> Result<PendingBatch, ClientError> {
+        let mut payload = OSCashierPayload::new(Actions::CreateEscrow, username.clone());
+        payload.set_receiver(beneficiary.clone());
+        payload.set_amount(amount);
+        payload.set_module(module);
+        payload.set_escrow_terms(arbiter, min_plugged, deadline);
+
+        let addresses = vec![
+            address::account(&username),
+            address::account(&beneficiary),
+            address::BLOCK_INFO_NAMESPACE.to_string()
+        ];
+
+        let payload_bytes = payload.to_bytes();
+
+        self.pending(self.create_transaction_for_addresses(payload_bytes, addresses)?)
+    }
+
+    /// Release pays the beneficiary, refund returns the points to `username`, `beneficiary` is read from the escrow if None
+    pub fn settle_escrow(&self, username: String, escrow_id: u64, release: bool, beneficiary: Option<String>) -> Result<PendingBatch, ClientError> {
+        let beneficiary = match beneficiary {
+            Some(beneficiary) => beneficiary,
+            None => match self.get_existing_account(&username)?.escrows.get(&escrow_id) {
+                Some(escrow) => escrow.get_beneficiary(),
+                None => return Err(ClientError::Validation(format!("No escrow #{} on \"{}\"", escrow_id, username)))
+            }
+        };
+
+        let action = if release { Actions::ReleaseEscrow } else { Actions::RefundEscrow };
+        let mut payload = OSCashierPayload::new(action, username.clone());
+        payload.set_escrow_id(escrow_id);
+
+        let addresses = vec![
+            address::account(&username),
+            address::account(&beneficiary),
+            address::BLOCK_INFO_NAMESPACE.to_string()
+        ];
+
+        let payload_bytes = payload.to_bytes();
+
+        self.pending(self.create_transaction_for_addresses(payload_bytes, addresses)?)
+    }
+
+    /// Replaces the role and budget of an existing member
+    pub fn set_member(&self, org: String, member_key: String, role: String, budget: f32) -> Result<PendingBatch, ClientError> {
+        let mut payload = OSCashierPayload::new(Actions::SetMember, org.clone());
+        payload.set_public_key(member_key);
+        payload.set_role(role);
+        payload.set_amount(budget);
 
         let payload_bytes = payload.to_bytes();
 
-        self.send_transaction(
-            &self.create_batchlist(
-                vec![self.create_batch(
-                    vec![self.create_transaction(payload_bytes, Some(vec![&sender,&receiver]))]
-                )]
-            )
-            .write_to_bytes()
-            .expect("Error: Couldn't serialise batchlist")
-        ).expect("Error: Couldn't send the Register request");
-    }
-
-    // pub fn list(&self, _list_modules: bool) {}
-    pub fn list_modules(&self) {
-        println!("Module -> Performance Benefit");
-        println!("=============================>");
-        self.module_performance.iter().for_each(|m| println!("{} -> {}", m.0, m.1));
+        self.pending(self.create_transaction(payload_bytes, Some(vec![&org]))?)
+    }
+
+    pub fn remove_member(&self, org: String, member_key: String) -> Result<PendingBatch, ClientError> {
+        let mut payload = OSCashierPayload::new(Actions::RemoveMember, org.clone());
+        payload.set_public_key(member_key);
+
+        let payload_bytes = payload.to_bytes();
+
+        self.pending(self.create_transaction(payload_bytes, Some(vec![&org]))?)
+    }
+}
+
+/// Signed transactions, not sent yet, in one batch
+///
+/// ```
+/// # use os_cashier::{keys, Client};
+/// # let client = Client::builder("http://localhost:8008").key(keys::random()?).build()?;
+/// let plug = client.plug("alice".to_string(), "slab_allocator".to_string())?;
+///
+/// // Applied only after `plug` is committed
+/// let claim = client.claim("alice".to_string(), "slab_allocator".to_string())?
+///                   .depends_on(&plug.transaction_ids())?;
+///
+/// // Or both in one batch, applied together or not at all
+/// let both = plug.and(claim);
+/// assert_eq!(both.transactions().len(), 2);
+/// # Ok::<(), os_cashier::ClientError>(())
+/// ```
+pub struct PendingBatch<'a> {
+    client: &'a Client,
+    transactions: Vec<Transaction>,
+}
+
+impl<'a> PendingBatch<'a> {
+    pub fn transactions(&self) -> &[Transaction] {
+        &self.transactions
+    }
+
+    /// Transaction ids are the transaction header signatures, in order
+    pub fn transaction_ids(&self) -> Vec<String> {
+        self.transactions.iter().map(|transaction| transaction.get_header_signature().to_string()).collect()
+    }
+
+    pub fn into_transactions(self) -> Vec<Transaction> {
+        self.transactions
+    }
+
+    /// Adds the transactions of `other` to this batch
+    pub fn and(mut self, other: PendingBatch) -> PendingBatch<'a> {
+        self.transactions.extend(other.transactions);
+        self
+    }
+
+    /// The validator applies these transactions only after the ones with `transaction_ids` are committed, they're signed again (so their ids change)
+    pub fn depends_on(self, transaction_ids: &[String]) -> Result<PendingBatch<'a>, ClientError> {
+        if transaction_ids.is_empty() {
+            return Ok(self)
+        }
+
+        let client = self.client;
+        let transactions = self.transactions.into_iter().map(|mut transaction| -> Result<Transaction, ClientError> {
+            let mut header = TransactionHeader::parse_from_bytes(transaction.get_header())?;
+            header.set_dependencies(protobuf::RepeatedField::from_vec(transaction_ids.to_vec()));
+
+            let header_bytes = header.write_to_bytes()?;
+            transaction.set_header_signature(client.sign_bytes(&header_bytes)?);
+            transaction.set_header(header_bytes);
+            Ok(transaction)
+        }).collect::<Result<Vec<Transaction>, ClientError>>()?;
+
+        Ok(PendingBatch { client, transactions })
+    }
+
+    pub fn to_batch_list(&self) -> Result<BatchList, ClientError> {
+        Ok(self.client.create_batchlist(vec![self.client.create_batch(self.transactions.clone())?]))
+    }
+
+    /// The serialized BatchList, to be posted later (see Client::submit) or elsewhere
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ClientError> {
+        Ok(self.to_batch_list()?.write_to_bytes()?)
+    }
+
+    /// Posts the batch, without waiting for it to be committed
+    pub fn submit(&self) -> Result<Submission, ClientError> {
+        self.client.submit(&self.to_bytes()?)
+    }
+
+    /// Posts the batch, and waits until it's committed, invalid, or the wait timeout passes
+    pub fn submit_and_wait(&self) -> Result<BatchStatus, ClientError> {
+        let submission = self.submit()?;
+        self.client.wait_for_batches(&submission.batch_ids)
     }
 }
@@ -0,0 +1,88 @@
+use std::fmt;
+
+// Exit codes, one per kind of failure, so scripts can tell them apart
+pub const EXIT_VALIDATION: i32 = 1;
+pub const EXIT_INVALID: i32 = 2;
+pub const EXIT_PENDING: i32 = 3;
+pub const EXIT_HTTP: i32 = 4;
+pub const EXIT_REST_API: i32 = 5;
+pub const EXIT_KEY: i32 = 6;
+pub const EXIT_SERIALIZATION: i32 = 7;
+
+#[derive(Debug)]
+pub enum ClientError {
+    Key(String),            // loading the private key, or signing with it
+    Serialization(String),  // protobuf/CBOR/base64 encoding or decoding
+    Http(reqwest::Error),   // couldn't reach the REST API
+    RestApi { status: u16, message: String },   // the REST API answered with an error
+    Validation(String),     // bad arguments, or state that makes the request pointless
+    BatchInvalid(String),   // the processor rejected the transaction, with its reason
+    BatchPending(String),   // not committed in time, with the batch status URL
+}
+
+impl ClientError {
+    // Stable name of the variant, for --output json
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ClientError::Key(_) => "key",
+            ClientError::Serialization(_) => "serialization",
+            ClientError::Http(_) => "http",
+            ClientError::RestApi { .. } => "rest_api",
+            ClientError::Validation(_) => "validation",
+            ClientError::BatchInvalid(_) => "invalid",
+            ClientError::BatchPending(_) => "pending",
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ClientError::Key(_) => EXIT_KEY,
+            ClientError::Serialization(_) => EXIT_SERIALIZATION,
+            ClientError::Http(_) => EXIT_HTTP,
+            ClientError::RestApi { .. } => EXIT_REST_API,
+            ClientError::Validation(_) => EXIT_VALIDATION,
+            ClientError::BatchInvalid(_) => EXIT_INVALID,
+            ClientError::BatchPending(_) => EXIT_PENDING,
+        }
+    }
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ClientError::Key(message) => write!(f, "Key error: {}", message),
+            ClientError::Serialization(message) => write!(f, "Serialization error: {}", message),
+            ClientError::Http(e) => write!(f, "Couldn't reach the REST API: {}", e),
+            ClientError::RestApi { status, message } => write!(f, "REST API error ({}): {}", status, message),
+            ClientError::Validation(message) => write!(f, "{}", message),
+            ClientError::BatchInvalid(message) => write!(f, "INVALID: {}", message),
+            ClientError::BatchPending(status_url) => write!(f, "PENDING: not committed yet, check {}", status_url),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<reqwest::Error> for ClientError {
+    fn from(e: reqwest::Error) -> Self {
+        ClientError::Http(e)
+    }
+}
+
+impl From<protobuf::ProtobufError> for ClientError {
+    fn from(e: protobuf::ProtobufError) -> Self {
+        ClientError::Serialization(e.to_string())
+    }
+}
+
+impl From<serde_cbor::Error> for ClientError {
+    fn from(e: serde_cbor::Error) -> Self {
+        ClientError::Serialization(e.to_string())
+    }
+}
+
+impl From<base64::DecodeError> for ClientError {
+    fn from(e: base64::DecodeError) -> Self {
+        ClientError::Serialization(e.to_string())
+    }
+}
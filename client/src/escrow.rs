@@ -0,0 +1,116 @@
+use serde_derive::{Serialize, Deserialize};
+
+// Released once the beneficiary has had `module` plugged for at least `min_seconds`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EscrowCondition {
+    module: String,
+    min_seconds: u64
+}
+
+#[allow(unused)]
+impl EscrowCondition {
+    pub fn new(module: String, min_seconds: u64) -> EscrowCondition {
+        EscrowCondition { module, min_seconds }
+    }
+
+    pub fn get_module(&self) -> String {
+        self.module.clone()
+    }
+
+    pub fn get_min_seconds(&self) -> u64 {
+        self.min_seconds
+    }
+
+    // `plugged_at` is the block timestamp the beneficiary plugged the module at, `now` the latest one, so every validator agrees
+    pub fn is_met(&self, plugged_at: Option<u64>, now: u64) -> bool {
+        plugged_at.map_or(false, |plugged_at| now.saturating_sub(plugged_at) >= self.min_seconds)
+    }
+}
+
+/*
+ * Points locked out of the payer's balance, for `beneficiary`
+ *
+ * Released by the payer, the arbiter, or anyone once the condition holds (before the deadline)
+ * Refunded by the arbiter or beneficiary at any time, or by anyone after the deadline
+ */
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Escrow {
+    beneficiary: String,
+    amount: f32,
+    arbiter: Option<String>,    // public key
+    condition: Option<EscrowCondition>,
+    deadline: u64   // chain timestamp
+}
+
+#[allow(unused)]
+impl Escrow {
+    pub fn new(beneficiary: String, amount: f32, arbiter: Option<String>, condition: Option<EscrowCondition>, deadline: u64) -> Escrow {
+        Escrow {
+            beneficiary,
+            amount,
+            arbiter,
+            condition,
+            deadline
+        }
+    }
+
+    pub fn get_beneficiary(&self) -> String {
+        self.beneficiary.clone()
+    }
+
+    pub fn get_amount(&self) -> f32 {
+        self.amount
+    }
+
+    pub fn get_arbiter(&self) -> Option<&String> {
+        self.arbiter.as_ref()
+    }
+
+    pub fn get_condition(&self) -> Option<&EscrowCondition> {
+        self.condition.as_ref()
+    }
+
+    pub fn get_deadline(&self) -> u64 {
+        self.deadline
+    }
+
+    pub fn is_arbiter(&self, key: &str) -> bool {
+        self.arbiter.as_ref().map_or(false, |arbiter| arbiter == key)
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        now > self.deadline
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn condition_needs_the_module_plugged() {
+        let condition = EscrowCondition::new("slab_allocator".to_string(), 60);
+        assert!(!condition.is_met(None, u64::MAX));
+    }
+
+    #[test]
+    fn condition_counts_from_the_plug() {
+        let condition = EscrowCondition::new("slab_allocator".to_string(), 60);
+        assert!(!condition.is_met(Some(1000), 1059));
+        assert!(condition.is_met(Some(1000), 1060));
+        assert!(condition.is_met(Some(1000), 5000));
+
+        // A block timestamp before the plug's (it can't go back, but shouldn't underflow either)
+        assert!(!condition.is_met(Some(1000), 900));
+    }
+
+    #[test]
+    fn escrow_expires_after_its_deadline() {
+        let escrow = Escrow::new("bob".to_string(), 5.0, Some("02ab".to_string()), None, 2000);
+        assert!(!escrow.is_expired(2000));
+        assert!(escrow.is_expired(2001));
+
+        assert!(escrow.is_arbiter("02ab"));
+        assert!(!escrow.is_arbiter("02cd"));
+    }
+}
@@ -0,0 +1,389 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use openssl::symm::{self, Cipher};
+use rand::{thread_rng, RngCore};
+use sawtooth_sdk::signing::{self, secp256k1::Secp256k1PrivateKey, PrivateKey};
+use serde_derive::{Deserialize, Serialize};
+
+use crate::error::ClientError;
+
+const PRIVATE_KEY_EXTENSION: &str = "priv";
+const PUBLIC_KEY_EXTENSION: &str = "pub";
+const PASSPHRASE_ENV: &str = "OS_CASHIER_KEY_PASSPHRASE";  // for scripts, instead of asking on the terminal
+
+// scrypt with N = 2^15, r = 8, p = 1 uses 32 MiB and takes a fraction of a second
+const SCRYPT_LOG_N: u8 = 15;
+const SCRYPT_R: u64 = 8;
+const SCRYPT_P: u64 = 1;
+const SCRYPT_MAX_MEMORY: u64 = 64 * 1024 * 1024;
+const KEYSTORE_VERSION: u32 = 1;
+
+/*
+ * An encrypted .priv file, JSON instead of the plain hex "sawtooth keygen" writes, the two are told apart by the first character
+ *
+ * The private key is encrypted with AES-256-GCM, under a key derived from the passphrase with scrypt, the KDF
+ * parameters are stored so they can be raised later without breaking older files
+ *
+ * The public key is the AEAD's associated data, so the file can't be edited to show another account's key
+ */
+#[derive(Serialize, Deserialize)]
+struct EncryptedKey {
+    version: u32,
+    public_key: String,     // not secret, lets "key show" and "key list" work without the passphrase
+    kdf: String,
+    log_n: u8,
+    r: u64,
+    p: u64,
+    salt: String,           // hex, as are the following
+    cipher: String,
+    nonce: String,
+    ciphertext: String,
+    tag: String,
+}
+
+/*
+ * Keys are kept like "sawtooth keygen" does, ~/.sawtooth/keys/<name>.priv holding the private key as hex,
+ * with <name>.pub next to it, so keys made by either tool work with the other
+ */
+pub fn keys_dir() -> Result<PathBuf, ClientError> {
+    match dirs::home_dir() {
+        Some(home_dir) => Ok(home_dir.join(".sawtooth").join("keys")),
+        None => Err(ClientError::Key("Couldn't get the home directory path ! OS may not be supported, pass a path with --key".to_string()))
+    }
+}
+
+// A key name ("alice" -> ~/.sawtooth/keys/alice.priv) or a path to a .priv file, None is the current OS user's key
+pub fn resolve(key: Option<&str>) -> Result<PathBuf, ClientError> {
+    let key = match key {
+        Some(key) => key.to_string(),
+        None => whoami::username()
+    };
+
+    if key.contains('/') || key.contains(std::path::MAIN_SEPARATOR) || key.ends_with(&format!(".{}", PRIVATE_KEY_EXTENSION)) {
+        return Ok(PathBuf::from(key))
+    }
+
+    check_name(&key)?;
+    Ok(keys_dir()?.join(format!("{}.{}", key, PRIVATE_KEY_EXTENSION)))
+}
+
+fn check_name(name: &str) -> Result<(), ClientError> {
+    if name.is_empty() || name.starts_with('.') || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.') {
+        return Err(ClientError::Validation(format!("Invalid key name: \"{}\"", name)))
+    }
+    Ok(())
+}
+
+pub fn from_hex(key_hex: &str) -> Result<Secp256k1PrivateKey, ClientError> {
+    Secp256k1PrivateKey::from_hex(key_hex.trim())
+        .map_err(|e| ClientError::Key(format!("Not a valid private key: {}", e)))
+}
+
+pub fn load(path: &Path) -> Result<Secp256k1PrivateKey, ClientError> {
+    let key_hex = match fs::read_to_string(path) {
+        Ok(key_hex) => key_hex,
+        Err(e) => return Err(ClientError::Key(format!("Couldn't read {}: {}", path.display(), e)))
+    };
+
+    if is_encrypted(&key_hex) {
+        let encrypted: EncryptedKey = serde_json::from_str(&key_hex)
+            .map_err(|e| ClientError::Key(format!("{} isn't a valid encrypted key: {}", path.display(), e)))?;
+        let passphrase = read_passphrase(&format!("Passphrase for {}: ", path.display()))?;
+
+        return decrypt(&encrypted, &passphrase)
+            .map_err(|e| ClientError::Key(format!("Couldn't decrypt {}: {}", path.display(), e)))
+    }
+
+    Secp256k1PrivateKey::from_hex(key_hex.trim())
+        .map_err(|e| ClientError::Key(format!("{} doesn't hold a valid private key: {}", path.display(), e)))
+}
+
+// Like public_key(load(path)), but doesn't ask for the passphrase of an encrypted key
+pub fn load_public_key(path: &Path) -> Result<String, ClientError> {
+    let key_hex = match fs::read_to_string(path) {
+        Ok(key_hex) => key_hex,
+        Err(e) => return Err(ClientError::Key(format!("Couldn't read {}: {}", path.display(), e)))
+    };
+
+    if is_encrypted(&key_hex) {
+        return serde_json::from_str::<EncryptedKey>(&key_hex)
+            .map(|encrypted| encrypted.public_key)
+            .map_err(|e| ClientError::Key(format!("{} isn't a valid encrypted key: {}", path.display(), e)))
+    }
+
+    public_key(&from_hex(&key_hex)?)
+}
+
+fn is_encrypted(contents: &str) -> bool {
+    contents.trim_start().starts_with('{')
+}
+
+pub fn random() -> Result<Secp256k1PrivateKey, ClientError> {
+    let context = match signing::create_context("secp256k1") {
+        Ok(context) => context,
+        Err(e) => return Err(ClientError::Key(format!("Couldn't create SECP256k1 context: {}", e)))
+    };
+
+    context.new_random_private_key()
+           .and_then(|random_key| Secp256k1PrivateKey::from_hex(&random_key.as_hex()))
+           .map_err(|e| ClientError::Key(format!("Couldn't generate a random key: {}", e)))
+}
+
+pub fn public_key(privatekey: &Secp256k1PrivateKey) -> Result<String, ClientError> {
+    let context = match signing::create_context("secp256k1") {
+        Ok(context) => context,
+        Err(e) => return Err(ClientError::Key(format!("Couldn't create SECP256k1 context: {}", e)))
+    };
+
+    match signing::CryptoFactory::new(context.as_ref()).new_signer(privatekey).get_public_key() {
+        Ok(public_key) => Ok(public_key.as_hex()),
+        Err(e) => Err(ClientError::Key(format!("Couldn't get the public key: {}", e)))
+    }
+}
+
+/*
+ * Writes <name>.priv (readable only by the user) and <name>.pub, returns the path of the private key
+ *
+ * With `encrypt` the .priv file is encrypted with a new passphrase, otherwise it's plain hex, usable by the sawtooth tools too
+ */
+pub fn save(name: &str, privatekey: &Secp256k1PrivateKey, force: bool, encrypt: bool) -> Result<PathBuf, ClientError> {
+    check_name(name)?;
+
+    let keys_dir = keys_dir()?;
+    let private_path = keys_dir.join(format!("{}.{}", name, PRIVATE_KEY_EXTENSION));
+    let public_path = keys_dir.join(format!("{}.{}", name, PUBLIC_KEY_EXTENSION));
+
+    if !force && (private_path.exists() || public_path.exists()) {
+        return Err(ClientError::Validation(format!("Key \"{}\" already exists in {}, pass --force to overwrite it", name, keys_dir.display())))
+    }
+
+    let public_key = public_key(privatekey)?;
+    let contents = if encrypt {
+        encrypt_key(privatekey, &public_key, &new_passphrase()?)?
+    } else {
+        privatekey.as_hex()
+    };
+
+    fs::create_dir_all(&keys_dir)
+        .and_then(|_| write_private(&private_path, &contents))
+        .and_then(|_| fs::write(&public_path, format!("{}\n", public_key)))
+        .map_err(|e| ClientError::Key(format!("Couldn't write the key to {}: {}", keys_dir.display(), e)))?;
+
+    Ok(private_path)
+}
+
+/*
+ * Encrypts the .priv files of plain keys in place, with one passphrase for all of them, returns the names of those encrypted
+ *
+ * Keys that are already encrypted are skipped, so running it again is harmless
+ */
+pub fn encrypt_existing(names: &[String]) -> Result<Vec<String>, ClientError> {
+    let names = if names.is_empty() {
+        list()?.into_iter().map(|(name, _)| name).collect()
+    } else {
+        names.to_vec()
+    };
+
+    let mut plain_keys = Vec::new();
+    for name in names {
+        let path = resolve(Some(&name))?;
+        let contents = fs::read_to_string(&path)
+            .map_err(|e| ClientError::Key(format!("Couldn't read {}: {}", path.display(), e)))?;
+
+        if !is_encrypted(&contents) {
+            plain_keys.push((name, path, from_hex(&contents)?));
+        }
+    }
+    if plain_keys.is_empty() {
+        return Ok(Vec::new())
+    }
+
+    let passphrase = new_passphrase()?;
+    let mut encrypted = Vec::new();
+    for (name, path, privatekey) in plain_keys {
+        let contents = encrypt_key(&privatekey, &public_key(&privatekey)?, &passphrase)?;
+
+        // Written next to it then renamed, so a failure never leaves a half written key
+        let tmp_path = path.with_extension(format!("{}.tmp", PRIVATE_KEY_EXTENSION));
+        write_private(&tmp_path, &contents)
+            .and_then(|_| fs::rename(&tmp_path, &path))
+            .map_err(|e| ClientError::Key(format!("Couldn't write {}: {}", path.display(), e)))?;
+
+        encrypted.push(name);
+    }
+
+    Ok(encrypted)
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], log_n: u8, r: u64, p: u64) -> Result<[u8; 32], ClientError> {
+    let mut key = [0u8; 32];
+    openssl::pkcs5::scrypt(passphrase.as_bytes(), salt, 1 << log_n, r, p, SCRYPT_MAX_MEMORY, &mut key)
+        .map_err(|e| ClientError::Key(format!("Couldn't derive the key from the passphrase: {}", e)))?;
+
+    Ok(key)
+}
+
+fn encrypt_key(privatekey: &Secp256k1PrivateKey, public_key: &str, passphrase: &str) -> Result<String, ClientError> {
+    let mut salt = [0u8; 16];
+    let mut nonce = [0u8; 12];
+    thread_rng().fill_bytes(&mut salt);
+    thread_rng().fill_bytes(&mut nonce);
+
+    let key = derive_key(passphrase, &salt, SCRYPT_LOG_N, SCRYPT_R, SCRYPT_P)?;
+    let key_bytes = hex::decode(privatekey.as_hex())
+        .map_err(|e| ClientError::Key(format!("Couldn't encode the private key: {}", e)))?;
+
+    let mut tag = [0u8; 16];
+    let ciphertext = symm::encrypt_aead(Cipher::aes_256_gcm(), &key, Some(&nonce), public_key.as_bytes(), &key_bytes, &mut tag)
+        .map_err(|e| ClientError::Key(format!("Couldn't encrypt the private key: {}", e)))?;
+
+    let encrypted = EncryptedKey {
+        version: KEYSTORE_VERSION,
+        public_key: public_key.to_string(),
+        kdf: "scrypt".to_string(),
+        log_n: SCRYPT_LOG_N,
+        r: SCRYPT_R,
+        p: SCRYPT_P,
+        salt: hex::encode(salt),
+        cipher: "aes-256-gcm".to_string(),
+        nonce: hex::encode(nonce),
+        ciphertext: hex::encode(ciphertext),
+        tag: hex::encode(tag),
+    };
+
+    serde_json::to_string_pretty(&encrypted)
+        .map_err(|e| ClientError::Serialization(e.to_string()))
+}
+
+// The error is only the reason, the caller knows which file it was
+fn decrypt(encrypted: &EncryptedKey, passphrase: &str) -> Result<Secp256k1PrivateKey, String> {
+    if encrypted.version != KEYSTORE_VERSION || encrypted.kdf != "scrypt" || encrypted.cipher != "aes-256-gcm" || encrypted.log_n > 20 {
+        return Err(format!("unsupported format (version {}, {}, {})", encrypted.version, encrypted.kdf, encrypted.cipher))
+    }
+
+    let decode = |field: &str| hex::decode(field).map_err(|e| format!("malformed hex: {}", e));
+    let salt = decode(&encrypted.salt)?;
+    let nonce = decode(&encrypted.nonce)?;
+    let ciphertext = decode(&encrypted.ciphertext)?;
+    let tag = decode(&encrypted.tag)?;
+
+    let key = derive_key(passphrase, &salt, encrypted.log_n, encrypted.r, encrypted.p).map_err(|e| e.to_string())?;
+    let key_bytes = symm::decrypt_aead(Cipher::aes_256_gcm(), &key, Some(&nonce), encrypted.public_key.as_bytes(), &ciphertext, &tag)
+        .map_err(|_| "wrong passphrase, or the file was modified".to_string())?;
+
+    let privatekey = Secp256k1PrivateKey::from_hex(&hex::encode(key_bytes)).map_err(|e| e.to_string())?;
+    if public_key(&privatekey).map_err(|e| e.to_string())? != encrypted.public_key {
+        return Err("its public key doesn't match the private key".to_string())
+    }
+
+    Ok(privatekey)
+}
+
+// From PASSPHRASE_ENV if set, else asked on the terminal (prompt on stderr, keeping stdout for the output)
+fn read_passphrase(prompt: &str) -> Result<String, ClientError> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        return Ok(passphrase)
+    }
+
+    rpassword::prompt_password_stderr(prompt)
+        .map_err(|e| ClientError::Key(format!("Couldn't read the passphrase (or set {}): {}", PASSPHRASE_ENV, e)))
+}
+
+// Asked twice, as a typo would lock the key for good
+fn new_passphrase() -> Result<String, ClientError> {
+    let passphrase = read_passphrase("New passphrase: ")?;
+    if passphrase.is_empty() {
+        return Err(ClientError::Validation("The passphrase can't be empty".to_string()))
+    }
+    if std::env::var(PASSPHRASE_ENV).is_err() && read_passphrase("Repeat the passphrase: ")? != passphrase {
+        return Err(ClientError::Validation("The passphrases don't match".to_string()))
+    }
+
+    Ok(passphrase)
+}
+
+#[cfg(unix)]
+fn write_private(path: &Path, contents: &str) -> std::io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+    let mut file = fs::OpenOptions::new().write(true).create(true).truncate(true).mode(0o600).open(path)?;
+    file.set_permissions(fs::Permissions::from_mode(0o600))?;   // mode() only applies to new files
+    file.write_all(format!("{}\n", contents).as_bytes())
+}
+
+#[cfg(not(unix))]
+fn write_private(path: &Path, contents: &str) -> std::io::Result<()> {
+    fs::write(path, format!("{}\n", contents))
+}
+
+// (name, public key) of each key in keys_dir(), a key that can't be read has an empty public key
+pub fn list() -> Result<Vec<(String, String)>, ClientError> {
+    let keys_dir = keys_dir()?;
+    if !keys_dir.exists() {
+        return Ok(Vec::new())
+    }
+
+    let entries = fs::read_dir(&keys_dir)
+        .map_err(|e| ClientError::Key(format!("Couldn't read {}: {}", keys_dir.display(), e)))?;
+
+    let mut keys: Vec<(String, String)> = entries.filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().map_or(false, |extension| extension == PRIVATE_KEY_EXTENSION))
+        .filter_map(|path| {
+            let name = path.file_stem()?.to_string_lossy().to_string();
+            let public_key = load_public_key(&path).unwrap_or_default();
+            Some((name, public_key))
+        })
+        .collect();
+    keys.sort();
+
+    Ok(keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encrypted_key(privatekey: &Secp256k1PrivateKey, public_key: &str) -> EncryptedKey {
+        let contents = encrypt_key(privatekey, public_key, "correct horse").unwrap();
+        assert!(is_encrypted(&contents));
+        serde_json::from_str(&contents).unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let privatekey = random().unwrap();
+        let encrypted = encrypted_key(&privatekey, &public_key(&privatekey).unwrap());
+
+        assert_eq!(decrypt(&encrypted, "correct horse").unwrap().as_hex(), privatekey.as_hex());
+    }
+
+    #[test]
+    fn wrong_passphrase() {
+        let privatekey = random().unwrap();
+        let encrypted = encrypted_key(&privatekey, &public_key(&privatekey).unwrap());
+
+        assert_eq!(decrypt(&encrypted, "battery staple").err().unwrap(), "wrong passphrase, or the file was modified");
+    }
+
+    #[test]
+    fn tampered_public_key() {
+        let privatekey = random().unwrap();
+        let mut encrypted = encrypted_key(&privatekey, &public_key(&privatekey).unwrap());
+
+        // Another account's key shown in the file fails the AEAD tag
+        encrypted.public_key = public_key(&random().unwrap()).unwrap();
+        assert_eq!(decrypt(&encrypted, "correct horse").err().unwrap(), "wrong passphrase, or the file was modified");
+    }
+
+    #[test]
+    fn mismatched_public_key() {
+        // Authenticated, but not the private key's own public key
+        let privatekey = random().unwrap();
+        let encrypted = encrypted_key(&privatekey, &public_key(&random().unwrap()).unwrap());
+
+        assert_eq!(decrypt(&encrypted, "correct horse").err().unwrap(), "its public key doesn't match the private key");
+    }
+}
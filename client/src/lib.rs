@@ -0,0 +1,58 @@
+//! Client library of the os-cashier transaction family, the `os-cashier-cli` binary is built on it
+//!
+//! Transactions are built and signed by a Client, and sent with their PendingBatch:
+//!
+//! ```
+//! use os_cashier::{keys, Client};
+//!
+//! let client = Client::builder("http://localhost:8008")
+//!     .key(keys::random()?)
+//!     .build()?;
+//!
+//! let pending = client.plug("alice".to_string(), "slab_allocator".to_string())?;
+//! assert_eq!(pending.transaction_ids().len(), 1);
+//!
+//! // Nothing is sent yet, the signed BatchList can also be posted later, or by another machine
+//! let batch_list_bytes = pending.to_bytes()?;
+//! assert_eq!(os_cashier::Client::get_batch_ids(&batch_list_bytes)?.len(), 1);
+//! # Ok::<(), os_cashier::ClientError>(())
+//! ```
+//!
+//! Submitting, waiting for the batch, and reading the state need a validator's REST API:
+//!
+//! ```no_run
+//! use os_cashier::{keys, BatchStatus, Client};
+//!
+//! let client = Client::builder("http://localhost:8008")
+//!     .key_file(keys::resolve(Some("alice"))?)    // ~/.sawtooth/keys/alice.priv
+//!     .build()?;
+//!
+//! match client.plug("alice".to_string(), "slab_allocator".to_string())?.submit_and_wait()? {
+//!     BatchStatus::Committed => println!("Plugged"),
+//!     BatchStatus::Invalid(reason) => eprintln!("Rejected: {}", reason),
+//!     BatchStatus::Pending => eprintln!("Not committed yet"),
+//! }
+//!
+//! let account = client.get_existing_account("alice")?;
+//! println!("{} points, claimable {:?}", account.points, client.claimable(&account, os_cashier::util::get_timestamp_sec()));
+//! # Ok::<(), os_cashier::ClientError>(())
+//! ```
+
+// escrow, payload, pow, proposal, schedule and username are shared with the processor (symlinked into
+// processor/src), so both sides enforce the same rules
+pub mod address;
+pub mod client;
+pub mod error;
+pub mod escrow;
+pub mod keys;
+pub mod mnemonic;
+pub mod payload;
+pub mod pow;
+pub mod proposal;
+pub mod schedule;
+pub mod state;
+pub mod username;
+pub mod util;
+
+pub use client::{BatchStatus, Client, ClientBuilder, PendingBatch, Submission};
+pub use error::ClientError;
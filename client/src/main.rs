@@ -1,13 +1,39 @@
-use clap::{AppSettings, clap_app};
+use clap::{App, AppSettings, ArgMatches, clap_app};
 use std::process;
 
-mod util;
-mod client;
-mod payload;
-use client::OSCashierClient;
+mod cli;
+mod recipients;
+mod output;
+mod script;
+use cli::OSCashierClient;
+use os_cashier::{address, keys, mnemonic, util, ClientError};
+use output::{OutputFormat, Report};
+use os_cashier::payload::{OSCashierPayload, Actions};
+
+const MNEMONIC_GAP_LIMIT: u32 = 5;  // restoring stops after this many derived keys in a row own no account
 
 fn main() {
-    let matches = clap_app!(The_OS_Cashier => 
+    let matches = app().get_matches();
+
+    // Parsed outside of run(), errors are printed in this format too
+    let output = match OutputFormat::from_string(matches.value_of("output").unwrap_or("table")) {
+        Some(output) => output,
+        None => {
+            let e = ClientError::Validation("--output must be one of json, table or plain".to_string());
+            output::print_error(&e, OutputFormat::Table);
+            process::exit(e.exit_code());
+        }
+    };
+
+    if let Err(e) = run(&matches, output) {
+        output::print_error(&e, output);
+        process::exit(e.exit_code());
+    }
+}
+
+// Also parses each step of a script, see run_script()
+fn app() -> App<'static> {
+    clap_app!(The_OS_Cashier => 
                     (setting: AppSettings::ColoredHelp)
                     (version: "0.1")
 //                    (author: "Aditya Gupta <ag15035@gmail.com>")
@@ -15,14 +41,51 @@ fn main() {
                     (@subcommand list => 
                         (setting: AppSettings::ColoredHelp)
                         (about: "Lists available modules")
-                        // (about: "Lists current users (with any plugged modules) or modules")
-                        // (@arg modules: "(Optional Arg) List modules")
+                     )
+                    (@subcommand users => 
+                        (setting: AppSettings::ColoredHelp)
+                        (about: "Lists registered users, with their points and plugged modules")
+                     )
+                    (@subcommand balance => 
+                        (setting: AppSettings::ColoredHelp)
+                        (about: "Show the points of a user")
+                        (@arg user: +required "Username of user")
+                     )
+                    (@subcommand show => 
+                        (setting: AppSettings::ColoredHelp)
+                        (about: "Show an account, its key, points and plugged modules")
+                        (@arg user: +required "Username of user")
                      )
                     (@arg url: --url +takes_value "URL of the REST API")
+                    (@arg no_wait: --("no-wait") "Don't wait for the transaction to be committed, just submit it")
+                    (@arg output: --output +takes_value "Output format: table (default), json or plain")
+                    (@arg key: --key +takes_value "Key to sign with, a name in ~/.sawtooth/keys or a path to a .priv file (default: your OS username)")
+                    (@arg ephemeral: --ephemeral "Sign with a random key that is thrown away afterwards")
+                    (@arg offline: --offline "Don't submit transactions, write the signed batch to the -o file (see submit, inspect)")
+                    (@arg out: -o --out +takes_value "File the signed batch is written to, with --offline")
+                    (@subcommand whoami => 
+                        (setting: AppSettings::ColoredHelp)
+                        (about: "List the accounts owned by your key")
+                     )
                     (@subcommand register => 
                         (setting: AppSettings::ColoredHelp)
                         (about: "Register a new user")
                         (@arg user: +required "Username of the new user")
+                        (@arg sponsor: --sponsor +takes_value "Your existing account, that vouches for (or pays the fee of) the new one")
+                        (@arg owner: --owner +takes_value "Public key (hex) that will own the account, needs --sponsor")
+                        (@arg pow_difficulty: --("pow-difficulty") +takes_value "Bits of the registration puzzle, 0 for none (default: from the chain's settings)")
+                     )
+                    (@subcommand deregister => 
+                        (setting: AppSettings::ColoredHelp)
+                        (about: "Close your account, all modules must be unplugged first")
+                        (@arg user: +required "Username of user")
+                        (@arg sweep_to: "Username that receives the remaining balance (default: the treasury)")
+                     )
+                    (@subcommand reap => 
+                        (setting: AppSettings::ColoredHelp)
+                        (about: "[Admin] Close an account that has been dormant for os_cashier.dormant_period")
+                        (@arg user: +required "Username of the dormant user")
+                        (@arg owner_key: --("owner-key") +takes_value "Public key (hex) owning the account (default: looked up)")
                      )
                     (@subcommand plug => 
                         (setting: AppSettings::ColoredHelp)
@@ -40,38 +103,506 @@ fn main() {
                         (setting: AppSettings::ColoredHelp)
                         (about: "Transfer asset")
                         (@arg sender: +required "Username that sends the coins")
-                        (@arg reciever: +required "Username that receives the coins")
+                        (@arg receiver: +required "Username that receives the coins")
                         (@arg amount: +required "Transaction amount")
+                        (@arg memo: --memo +takes_value "Note attached to the transfer")
+                        (@arg idempotency_key: --("idempotency-key") +takes_value "Any unique string, resending with the same key won't pay twice")
+                     )
+                    (@subcommand ("multi-transfer") => 
+                        (setting: AppSettings::ColoredHelp)
+                        (about: "Pay several users at once, in a single transaction")
+                        (@arg sender: +required "Username that sends the coins")
+                        (@arg file: +required "CSV (receiver,amount per line) or JSON ({\"receiver\": amount}) file of payments")
+                        (@arg memo: --memo +takes_value "Note attached to the transfer")
+                        (@arg idempotency_key: --("idempotency-key") +takes_value "Any unique string, resending with the same key won't pay twice")
+                     )
+                    (@subcommand claim => 
+                        (setting: AppSettings::ColoredHelp)
+                        (about: "Claim reward accrued by a plugged module, without unplugging it")
+                        (@arg user: +required "Username of user")
+                        (@arg module: +required "Name of the plugged module")
+                     )
+                    (@subcommand reboot => 
+                        (setting: AppSettings::ColoredHelp)
+                        (about: "Unplug and settle all modules at once, optionally plugging a new set")
+                        (@arg user: +required "Username of user")
+                        (@arg modules: ... "Modules to plug after the reboot")
                      )
-                    ).get_matches();
+                    (@subcommand key => 
+                        (setting: AppSettings::ColoredHelp)
+                        (setting: AppSettings::SubcommandRequiredElseHelp)
+                        (about: "Manage your keys, and the key that owns an account")
+                        (@subcommand generate => 
+                            (setting: AppSettings::ColoredHelp)
+                            (about: "Generate a new key in ~/.sawtooth/keys")
+                            (@arg name: "Name of the key (default: your OS username)")
+                            (@arg force: --force "Overwrite the key if it exists")
+                            (@arg encrypt: --encrypt "Encrypt it with a passphrase (sawtooth tools can't read it then)")
+                         )
+                        (@subcommand import => 
+                            (setting: AppSettings::ColoredHelp)
+                            (about: "Save an existing private key in ~/.sawtooth/keys")
+                            (@arg name: +required "Name of the key")
+                            (@arg private_key: +required "Private key (hex), or a file holding it")
+                            (@arg force: --force "Overwrite the key if it exists")
+                            (@arg encrypt: --encrypt "Encrypt it with a passphrase (sawtooth tools can't read it then)")
+                         )
+                        (@subcommand show => 
+                            (setting: AppSettings::ColoredHelp)
+                            (about: "Show the public key of a key (default: the one --key selects)")
+                            (@arg name: "Name or path of the key")
+                         )
+                        (@subcommand list => 
+                            (setting: AppSettings::ColoredHelp)
+                            (about: "List the keys in ~/.sawtooth/keys")
+                         )
+                        (@subcommand encrypt => 
+                            (setting: AppSettings::ColoredHelp)
+                            (about: "Encrypt existing plain keys with a passphrase")
+                            (@arg names: ... "Names or paths of the keys (default: every key in ~/.sawtooth/keys)")
+                         )
+                        (@subcommand mnemonic => 
+                            (setting: AppSettings::ColoredHelp)
+                            (setting: AppSettings::SubcommandRequiredElseHelp)
+                            (about: "Keys derived from one mnemonic phrase, saved as <name>-<index>")
+                            (@subcommand create => 
+                                (setting: AppSettings::ColoredHelp)
+                                (about: "Create a new mnemonic, and save its first keys")
+                                (@arg name: "Prefix of the saved key names (default: your OS username)")
+                                (@arg words: --words +takes_value "Number of words, 12 (default) to 24")
+                                (@arg accounts: --accounts +takes_value "Number of keys to save (default: 1)")
+                                (@arg encrypt: --encrypt "Encrypt the saved keys with a passphrase")
+                                (@arg force: --force "Overwrite keys that exist")
+                             )
+                            (@subcommand restore => 
+                                (setting: AppSettings::ColoredHelp)
+                                (about: "Save the keys of a mnemonic again, asks for the phrase (or reads OS_CASHIER_MNEMONIC)")
+                                (@arg name: "Prefix of the saved key names (default: your OS username)")
+                                (@arg accounts: --accounts +takes_value "Number of keys to save (default: those owning accounts)")
+                                (@arg encrypt: --encrypt "Encrypt the saved keys with a passphrase")
+                                (@arg force: --force "Overwrite keys that exist")
+                             )
+                            (@subcommand list => 
+                                (setting: AppSettings::ColoredHelp)
+                                (about: "List the keys of a mnemonic and the accounts they own, asks for the phrase (or reads OS_CASHIER_MNEMONIC)")
+                                (@arg accounts: --accounts +takes_value "Number of keys to list (default: those owning accounts)")
+                             )
+                         )
+                        (@subcommand rotate => 
+                            (setting: AppSettings::ColoredHelp)
+                            (about: "Replace the account's public key, sign with the current or the recovery key")
+                            (@arg user: +required "Username of user")
+                            (@arg new_key: +required "New public key (hex)")
+                            (@arg old_key: --("old-key") +takes_value "Current public key (hex) of the account (default: looked up)")
+                         )
+                        (@subcommand ("set-recovery") => 
+                            (setting: AppSettings::ColoredHelp)
+                            (about: "Set a recovery key, that can rotate the key after a timelock")
+                            (@arg user: +required "Username of user")
+                            (@arg recovery_key: +required "Recovery public key (hex), pass \"\" to remove it")
+                            (@arg timelock: "Seconds the recovery key has to wait before rotating (default: 7 days)")
+                         )
+                     )
+                    (@subcommand multisig => 
+                        (setting: AppSettings::ColoredHelp)
+                        (setting: AppSettings::SubcommandRequiredElseHelp)
+                        (about: "M-of-N accounts, where operations need approval from multiple keys")
+                        (@subcommand ("set-signers") => 
+                            (setting: AppSettings::ColoredHelp)
+                            (about: "Make the account multisig, with the given signer keys (none to turn it off)")
+                            (@arg user: +required "Username of user")
+                            (@arg threshold: +required "Approvals required")
+                            (@arg keys: ... "Signer public keys (hex)")
+                         )
+                        (@subcommand propose => 
+                            (setting: AppSettings::ColoredHelp)
+                            (setting: AppSettings::SubcommandRequiredElseHelp)
+                            (about: "Propose an operation on a multisig account")
+                            (@subcommand transfer => 
+                                (setting: AppSettings::ColoredHelp)
+                                (@arg user: +required "Multisig account that sends the coins")
+                                (@arg receiver: +required "Username that receives the coins")
+                                (@arg amount: +required "Transaction amount")
+                             )
+                            (@subcommand plug => 
+                                (setting: AppSettings::ColoredHelp)
+                                (@arg user: +required "Multisig account")
+                                (@arg module: +required "Name of pre-available module")
+                             )
+                            (@subcommand unplug => 
+                                (setting: AppSettings::ColoredHelp)
+                                (@arg user: +required "Multisig account")
+                                (@arg module: +required "Name of pre-available module")
+                             )
+                            (@subcommand ("set-signers") => 
+                                (setting: AppSettings::ColoredHelp)
+                                (@arg user: +required "Multisig account")
+                                (@arg threshold: +required "Approvals required")
+                                (@arg keys: ... "Signer public keys (hex)")
+                             )
+                         )
+                        (@subcommand approve => 
+                            (setting: AppSettings::ColoredHelp)
+                            (about: "Approve a pending proposal, it executes once enough signers approve")
+                            (@arg user: +required "Multisig account")
+                            (@arg id: +required "Proposal id, see \"multisig pending\"")
+                            (@arg receiver: --receiver +takes_value "Receiver of the proposed transfer, \"\" if it isn't a transfer (default: looked up)")
+                         )
+                        (@subcommand pending => 
+                            (setting: AppSettings::ColoredHelp)
+                            (about: "List pending proposals")
+                            (@arg user: +required "Multisig account")
+                         )
+                     )
+                    (@subcommand name => 
+                        (setting: AppSettings::ColoredHelp)
+                        (setting: AppSettings::SubcommandRequiredElseHelp)
+                        (about: "Give away, sell or buy accounts (name, balance and modules)")
+                        (@subcommand transfer => 
+                            (setting: AppSettings::ColoredHelp)
+                            (about: "Give your account to another key")
+                            (@arg user: +required "Your account")
+                            (@arg new_key: +required "Public key (hex) of the new owner")
+                         )
+                        (@subcommand list => 
+                            (setting: AppSettings::ColoredHelp)
+                            (about: "Put your account up for sale, a price of 0 takes it down")
+                            (@arg user: +required "Your account")
+                            (@arg price: +required "Price in CPUCoins")
+                            (@arg payout: +required "Account that receives the price")
+                         )
+                        (@subcommand buy => 
+                            (setting: AppSettings::ColoredHelp)
+                            (about: "Buy a listed account")
+                            (@arg user: +required "Account to buy")
+                            (@arg from: +required "Your account that pays")
+                            (@arg max_price: +required "Most you agree to pay")
+                            (@arg seller_key: --("seller-key") +takes_value "Public key (hex) of the seller, with --payout (default: looked up)")
+                            (@arg payout: --payout +takes_value "Account the listing pays out to, with --seller-key (default: looked up)")
+                         )
+                        (@subcommand offer => 
+                            (setting: AppSettings::ColoredHelp)
+                            (about: "Offer to buy an account, a price of 0 withdraws the offer")
+                            (@arg user: +required "Account to buy")
+                            (@arg from: +required "Your account that pays")
+                            (@arg price: +required "Offered price")
+                         )
+                        (@subcommand accept => 
+                            (setting: AppSettings::ColoredHelp)
+                            (about: "Sell your account to an offer")
+                            (@arg user: +required "Your account")
+                            (@arg buyer: +required "Account that made the offer")
+                            (@arg payout: +required "Account that receives the price")
+                            (@arg buyer_key: --("buyer-key") +takes_value "Public key (hex) of the buyer (default: looked up)")
+                         )
+                        (@subcommand offers => 
+                            (setting: AppSettings::ColoredHelp)
+                            (about: "Show the listing and offers for an account")
+                            (@arg user: +required "Username of user")
+                         )
+                     )
+                    (@subcommand allowance => 
+                        (setting: AppSettings::ColoredHelp)
+                        (setting: AppSettings::SubcommandRequiredElseHelp)
+                        (about: "Let other accounts spend from yours")
+                        (@subcommand grant => 
+                            (setting: AppSettings::ColoredHelp)
+                            (about: "Allow an account to spend up to amount, replaces any earlier allowance")
+                            (@arg user: +required "Your account")
+                            (@arg spender: +required "Account allowed to spend")
+                            (@arg amount: +required "Allowance")
+                         )
+                        (@subcommand revoke => 
+                            (setting: AppSettings::ColoredHelp)
+                            (about: "Remove an allowance")
+                            (@arg user: +required "Your account")
+                            (@arg spender: +required "Account to revoke")
+                         )
+                        (@subcommand show => 
+                            (setting: AppSettings::ColoredHelp)
+                            (about: "List allowances given by an account")
+                            (@arg user: +required "Username of user")
+                         )
+                        (@subcommand spend => 
+                            (setting: AppSettings::ColoredHelp)
+                            (about: "Transfer from an account that gave you an allowance")
+                            (@arg owner: +required "Account that gave the allowance")
+                            (@arg spender: +required "Your account, that has the allowance")
+                            (@arg receiver: +required "Username that receives the coins")
+                            (@arg amount: +required "Transaction amount")
+                         )
+                     )
+                    (@subcommand schedule => 
+                        (setting: AppSettings::ColoredHelp)
+                        (setting: AppSettings::SubcommandRequiredElseHelp)
+                        (about: "Standing orders, recurring transfers timed by the block timestamps")
+                        (@subcommand create => 
+                            (setting: AppSettings::ColoredHelp)
+                            (about: "Pay a user every interval, the first payment is due one interval from now")
+                            (@arg user: +required "Your account, that pays")
+                            (@arg receiver: +required "Username that receives the coins")
+                            (@arg amount: +required "Amount per payment")
+                            (@arg interval: +required "Time between payments, eg. 3600, 30m, 12h or 1d")
+                            (@arg runs: --runs +takes_value "Stop after this many payments")
+                            (@arg until: --until +takes_value "Stop after this unix timestamp")
+                         )
+                        (@subcommand cancel => 
+                            (setting: AppSettings::ColoredHelp)
+                            (about: "Cancel a standing order")
+                            (@arg user: +required "Your account")
+                            (@arg id: +required "Schedule id, see \"schedule list\"")
+                         )
+                        (@subcommand list => 
+                            (setting: AppSettings::ColoredHelp)
+                            (about: "List the standing orders of an account")
+                            (@arg user: +required "Username of user")
+                         )
+                        (@subcommand execute => 
+                            (setting: AppSettings::ColoredHelp)
+                            (about: "Pay out the due standing orders of an account, anyone can run this")
+                            (@arg user: +required "Username of the paying account")
+                            (@arg receivers: --receivers +takes_value "Comma separated receivers of the account's standing orders (default: looked up)")
+                         )
+                     )
+                    (@subcommand escrow => 
+                        (setting: AppSettings::ColoredHelp)
+                        (setting: AppSettings::SubcommandRequiredElseHelp)
+                        (about: "Lock coins for a user, paid out by an arbiter or once a condition holds")
+                        (@subcommand create => 
+                            (setting: AppSettings::ColoredHelp)
+                            (about: "Lock coins in escrow, needs --arbiter, --module or both")
+                            (@arg user: +required "Your account, that pays")
+                            (@arg beneficiary: +required "Username that receives the coins on release")
+                            (@arg amount: +required "Amount to lock")
+                            (@arg expires_in: +required "Time after which anyone can refund it, eg. 3600, 12h or 7d")
+                            (@arg arbiter: --arbiter +takes_value "Public key (hex) that may release or refund it")
+                            (@arg module: --module +takes_value "Release once the beneficiary has this module plugged...")
+                            (@arg plugged_for: --("plugged-for") +takes_value "...for this long, eg. 1h (default: 0)")
+                         )
+                        (@subcommand release => 
+                            (setting: AppSettings::ColoredHelp)
+                            (about: "Pay out an escrow to its beneficiary")
+                            (@arg user: +required "Account that created the escrow")
+                            (@arg id: +required "Escrow id, see \"escrow list\"")
+                            (@arg beneficiary: --beneficiary +takes_value "Beneficiary of the escrow (default: looked up)")
+                         )
+                        (@subcommand refund => 
+                            (setting: AppSettings::ColoredHelp)
+                            (about: "Return an escrow to the account that created it")
+                            (@arg user: +required "Account that created the escrow")
+                            (@arg id: +required "Escrow id, see \"escrow list\"")
+                            (@arg beneficiary: --beneficiary +takes_value "Beneficiary of the escrow (default: looked up)")
+                         )
+                        (@subcommand list => 
+                            (setting: AppSettings::ColoredHelp)
+                            (about: "List the open escrows created by an account")
+                            (@arg user: +required "Username of user")
+                         )
+                     )
+                    (@subcommand org => 
+                        (setting: AppSettings::ColoredHelp)
+                        (setting: AppSettings::SubcommandRequiredElseHelp)
+                        (about: "Organisation accounts, where other keys plug/unplug/claim modules by role")
+                        (@subcommand add => 
+                            (setting: AppSettings::ColoredHelp)
+                            (about: "Add a member, or change its role and budget, any account becomes an organisation with its first member")
+                            (@arg org: +required "Organisation account")
+                            (@arg key: +required "Public key (hex) of the member")
+                            (@arg role: +required "admin (manages members), member (plugs within a budget) or viewer")
+                            (@arg budget: --budget +takes_value "CPUCoins a member may spend plugging modules (default: 0)")
+                         )
+                        (@subcommand remove => 
+                            (setting: AppSettings::ColoredHelp)
+                            (about: "Remove a member, or leave an organisation")
+                            (@arg org: +required "Organisation account")
+                            (@arg key: +required "Public key (hex) of the member")
+                         )
+                        (@subcommand members => 
+                            (setting: AppSettings::ColoredHelp)
+                            (about: "List the members of an organisation")
+                            (@arg org: +required "Organisation account")
+                         )
+                        (@subcommand plug => 
+                            (setting: AppSettings::ColoredHelp)
+                            (about: "Plug a module on the organisation's behalf")
+                            (@arg org: +required "Organisation account")
+                            (@arg module: +required "Name of pre-available module")
+                         )
+                        (@subcommand unplug => 
+                            (setting: AppSettings::ColoredHelp)
+                            (about: "Unplug a module on the organisation's behalf")
+                            (@arg org: +required "Organisation account")
+                            (@arg module: +required "Name of pre-available module")
+                         )
+                     )
+                    (@subcommand claimable => 
+                        (setting: AppSettings::ColoredHelp)
+                        (about: "Show the reward claimable from each plugged module")
+                        (@arg user: +required "Username of user")
+                     )
+                    (@subcommand run => 
+                        (setting: AppSettings::ColoredHelp)
+                        (about: "Run the commands of a YAML script, as one atomic batch or as batches depending on each other")
+                        (@arg file: +required "Script file")
+                     )
+                    (@subcommand submit => 
+                        (setting: AppSettings::ColoredHelp)
+                        (about: "Submit a batch file signed with --offline")
+                        (@arg file: +required "Batch file")
+                     )
+                    (@subcommand inspect => 
+                        (setting: AppSettings::ColoredHelp)
+                        (about: "Decode a batch file, its transactions and payloads, and check its signatures")
+                        (@arg file: +required "Batch file")
+                     )
+                    )
+}
+
+// clap only checks that an argument is present, not that it parses
+fn parse_arg<T: std::str::FromStr>(value: &str, name: &str) -> Result<T, ClientError> {
+    value.parse().map_err(|_| ClientError::Validation(format!("Invalid {}: \"{}\"", name, value)))
+}
+
+// parse_arg takes "NaN" and "inf" for floats, no amount of points can be either
+fn parse_amount(value: &str, name: &str) -> Result<f32, ClientError> {
+    match parse_arg::<f32>(value, name)? {
+        amount if amount.is_finite() => Ok(amount),
+        _ => Err(ClientError::Validation(format!("Invalid {}: \"{}\"", name, value)))
+    }
+}
+
+/*
+ * A value the command would look up in the state of `account`, None if it's to be looked up
+ *
+ * There's no chain to look it up on when signing offline, and in a script, an earlier step may have changed the account
+ */
+fn lookup_arg(args: &ArgMatches, name: &str, account: Option<&str>, client: &OSCashierClient) -> Result<Option<String>, ClientError> {
+    let flag = name.replace('_', "-");
+
+    if let Some(value) = args.value_of(name) {
+        return Ok(Some(value.to_string()))
+    }
+    if client.is_offline() {
+        return Err(ClientError::Validation(format!("--{} is needed with --offline, it can't be looked up", flag)))
+    }
+    if let Some(account) = account {
+        if client.is_changed_by_script(&address::account(account))? {
+            return Err(ClientError::Validation(format!(
+                "--{} is needed, an earlier step changes {}, and it would be looked up as it was before the script", flag, account
+            )))
+        }
+    }
 
+    Ok(None)
+}
+
+fn run(matches: &ArgMatches, output: OutputFormat) -> Result<(), ClientError> {
     let rest_api_url = matches.value_of("url").unwrap_or(
             if cfg!(debug_assertions) {
                 "http://localhost:8008"
             } else { "http://rest-api:8008" }   // presuming release build will almost always run inside docker, so changing the defaults
         );
 
-    let client = OSCashierClient::new(
-        rest_api_url.to_string()
-    );
+    // Managing keys needs neither a key nor the REST API
+    if let Some(("key", key_matches)) = matches.subcommand() {
+        match key_matches.subcommand() {
+            Some((operation, args)) if ["generate", "import", "show", "list", "encrypt"].contains(&operation) => {
+                return manage_keys(operation, args, matches.value_of("key"), output)
+            },
+            _ => {}
+        }
+    }
+
+    let mut client = OSCashierClient::new(
+        rest_api_url.to_string(),
+        matches.value_of("key"),
+        matches.is_present("ephemeral"),
+        !matches.is_present("no_wait"),
+        output
+    )?;
+
+    match (matches.is_present("offline"), matches.value_of("out")) {
+        (true, Some(out)) => client.set_batch_file(std::path::PathBuf::from(out)),
+        (true, None) => return Err(ClientError::Validation("--offline needs -o <file> to write the signed batch to".to_string())),
+        (false, Some(_)) => return Err(ClientError::Validation("-o is only used with --offline".to_string())),
+        (false, None) => {}
+    }
 
     /* TODO:
      * Currently there is no good use of the key and signing, as anyone can plug/unplug or transfer in other's name... find some ways
      */
 
+    // The library doesn't know the command line, point to the ways of getting a key
+    dispatch(matches, &client, output).map_err(|e| match e {
+        ClientError::Key(message) if message.starts_with("No key") && client.api.get_public_key().is_err() => ClientError::Key(format!(
+            "{}, create one with \"key generate\" or \"key import\", or pass --ephemeral to use a throwaway key", message
+        )),
+        e => e
+    })
+}
+
+fn dispatch(matches: &ArgMatches, client: &OSCashierClient, output: OutputFormat) -> Result<(), ClientError> {
     match matches.subcommand() {
         Some(cmd) => {
             match cmd.0 {
                 "list" => {
-                    // client.list(cmd.1.is_present("modules"));
-                    client.list_modules();
+                    client.list_modules()?;
+                },
+                "users" => {
+                    client.users()?;
+                },
+                "balance" => {
+                    client.balance(cmd.1.value_of("user").unwrap().to_string())?;    // required by clap
+                },
+                "show" => {
+                    client.show(cmd.1.value_of("user").unwrap().to_string())?;
+                },
+                "whoami" => {
+                    client.whoami()?;
                 },
                 "register" => {
                     match cmd.1.value_of("user") {
-                        Some(username) => client.reg(username.to_string()),
+                        Some(username) => {
+                            let sponsor = cmd.1.value_of("sponsor").map(|sponsor| sponsor.to_string());
+                            let pow_difficulty = match sponsor {
+                                Some(_) => None,    // a sponsored registration needs no puzzle
+                                None => {
+                                    let difficulty = match lookup_arg(cmd.1, "pow_difficulty", None, client)? {
+                                        Some(bits) => parse_arg(&bits, "pow difficulty")?,
+                                        None => client.api.get_pow_difficulty()?
+                                    };
+                                    if difficulty > 0 {
+                                        eprintln!("Solving the registration puzzle ({} bits)...", difficulty);
+                                    }
+                                    Some(difficulty)
+                                }
+                            };
+
+                            client.send(client.api.reg(
+                                username.to_string(),
+                                sponsor,
+                                cmd.1.value_of("owner").map(|owner| owner.to_string()),
+                                pow_difficulty
+                            )?)?
+                        },
+                        None => {
+                            return Err(ClientError::Validation("Username required !".to_string()))
+                        }
+                    }
+                },
+                "deregister" => {
+                    match cmd.1.value_of("user") {
+                        Some(username) => client.send(client.api.deregister(username.to_string(), cmd.1.value_of("sweep_to").map(|u| u.to_string()))?)?,
+                        None => {
+                            return Err(ClientError::Validation("Username required !".to_string()))
+                        }
+                    }
+                },
+                "reap" => {
+                    match cmd.1.value_of("user") {
+                        Some(username) => client.send(client.api.reap(username.to_string(), lookup_arg(cmd.1, "owner_key", Some(username), client)?)?)?,
                         None => {
-                            println!("Username required !");
-                            process::exit(1);
+                            return Err(ClientError::Validation("Username required !".to_string()))
                         }
                     }
                 },
@@ -82,10 +613,9 @@ fn main() {
                     };
 
                     match cmd.1.value_of("module") {
-                        Some(module_name) => client.plug(username, module_name.to_string()),
+                        Some(module_name) => client.send(client.api.plug(username, module_name.to_string())?)?,
                         None => {
-                            println!("Module name required !\nTip: Use \"list modules\" subcommand");
-                            process::exit(1);
+                            return Err(ClientError::Validation("Module name required !\nTip: Use \"list modules\" subcommand".to_string()))
                         }
                     }
                 },
@@ -96,10 +626,9 @@ fn main() {
                     };
 
                     match cmd.1.value_of("module") {
-                        Some(module_name) => client.unplug(username, module_name.to_string()),
+                        Some(module_name) => client.send(client.api.unplug(username, module_name.to_string())?)?,
                         None => {
-                            println!("Module name required !\nTip: Use \"list modules\" subcommand");
-                            process::exit(1);
+                            return Err(ClientError::Validation("Module name required !\nTip: Use \"list modules\" subcommand".to_string()))
                         }
                     }
                 },
@@ -112,35 +641,600 @@ fn main() {
                     match cmd.1.value_of("receiver") {
                         Some(receiver) => {
                             match cmd.1.value_of("amount") {
-                                Some(amount) => client.transfer(sender, receiver.to_string(), amount.parse().unwrap()),    // convert amount from &str to number
+                                Some(amount) => {
+                                    client.send(client.api.transfer(
+                                        sender, receiver.to_string(), parse_amount(amount, "amount")?,
+                                        cmd.1.value_of("memo").map(|memo| memo.to_string()),
+                                        cmd.1.value_of("idempotency_key").map(|key| key.to_string())
+                                    )?)?
+                                },
                                 None => {
-                                    println!("Wrong request: Pass transaction amount!");
-                                    process::exit(1);
+                                    return Err(ClientError::Validation("Wrong request: Pass transaction amount!".to_string()))
                                 }
                             }
                         },
                         None => {
-                            println!("Wrong request: Pass receiver username!");
-                            process::exit(1);
+                            return Err(ClientError::Validation("Wrong request: Pass receiver username!".to_string()))
                         }
                     }
 
-                }
+                },
+                "multi-transfer" => {
+                    let sender = cmd.1.value_of("sender").unwrap().to_string();   // required by clap
+                    let recipients = match recipients::read_recipients(cmd.1.value_of("file").unwrap()) {
+                        Ok(recipients) => recipients,
+                        Err(reason) => {
+                            return Err(ClientError::Validation(reason))
+                        }
+                    };
+
+                    let memo = cmd.1.value_of("memo").map(|memo| memo.to_string());
+
+                    client.send(client.api.multi_transfer(sender, recipients, memo, cmd.1.value_of("idempotency_key").map(|key| key.to_string()))?)?;
+                },
+                "claim" => {
+                    let username = match cmd.1.value_of("user") {
+                        Some(username) => username.to_string(),
+                        None => whoami::username()
+                    };
+
+                    match cmd.1.value_of("module") {
+                        Some(module_name) => client.send(client.api.claim(username, module_name.to_string())?)?,
+                        None => {
+                            return Err(ClientError::Validation("Module name required !\nTip: Use \"claimable\" subcommand to see plugged modules".to_string()))
+                        }
+                    }
+                },
+                "reboot" => {
+                    let username = match cmd.1.value_of("user") {
+                        Some(username) => username.to_string(),
+                        None => whoami::username()
+                    };
+
+                    let module_names = match cmd.1.values_of("modules") {
+                        Some(modules) => modules.map(|m| m.to_string()).collect(),
+                        None => vec![]
+                    };
+
+                    client.send(client.api.reboot(username, module_names)?)?;
+                },
+                "key" => {
+                    match cmd.1.subcommand() {
+                        Some(("mnemonic", args)) => {
+                            manage_mnemonic(args, client, output)?;
+                        },
+                        Some(("rotate", args)) => {
+                            let username = args.value_of("user").unwrap().to_string();    // required by clap
+                            let new_key = args.value_of("new_key").unwrap().to_string();
+
+                            let old_key = lookup_arg(args, "old_key", Some(&username), client)?;
+
+                            client.send(client.api.rotate_key(username, new_key, old_key)?)?;
+                        },
+                        Some(("set-recovery", args)) => {
+                            let username = args.value_of("user").unwrap().to_string();
+                            let recovery_key = args.value_of("recovery_key").unwrap().to_string();
+                            let timelock = match args.value_of("timelock") {
+                                Some(timelock) => parse_arg(timelock, "timelock")?,
+                                None => 7 * 24 * 60 * 60
+                            };
+
+                            client.send(client.api.set_recovery(username, recovery_key, timelock)?)?;
+                        },
+                        _ => {
+                            return Err(ClientError::Validation("Unrecognised key operation ! Use \"key --help\" to see available options".to_string()))
+                        }
+                    }
+                },
+                "multisig" => {
+                    match cmd.1.subcommand() {
+                        Some(("set-signers", args)) => {
+                            let username = args.value_of("user").unwrap().to_string();    // required by clap
+                            let threshold = parse_arg(args.value_of("threshold").unwrap(), "threshold")?;
+                            let keys = match args.values_of("keys") {
+                                Some(keys) => keys.map(|k| k.to_string()).collect(),
+                                None => vec![]
+                            };
+
+                            client.send(client.api.set_signers(username, keys, threshold)?)?;
+                        },
+                        Some(("propose", propose_cmd)) => {
+                            let (username, operation) = match propose_cmd.subcommand() {
+                                Some(("transfer", args)) => {
+                                    let username = args.value_of("user").unwrap().to_string();
+                                    let mut operation = OSCashierPayload::new(Actions::Transfer, username.clone());
+                                    operation.set_receiver(args.value_of("receiver").unwrap().to_string());
+                                    operation.set_amount(parse_amount(args.value_of("amount").unwrap(), "amount")?);
+                                    (username, operation)
+                                },
+                                Some(("plug", args)) => {
+                                    let username = args.value_of("user").unwrap().to_string();
+                                    let mut operation = OSCashierPayload::new(Actions::PlugMod, username.clone());
+                                    operation.set_module(args.value_of("module").unwrap().to_string());
+                                    (username, operation)
+                                },
+                                Some(("unplug", args)) => {
+                                    let username = args.value_of("user").unwrap().to_string();
+                                    let mut operation = OSCashierPayload::new(Actions::UnplugMod, username.clone());
+                                    operation.set_module(args.value_of("module").unwrap().to_string());
+                                    (username, operation)
+                                },
+                                Some(("set-signers", args)) => {
+                                    let username = args.value_of("user").unwrap().to_string();
+                                    let mut operation = OSCashierPayload::new(Actions::SetSigners, username.clone());
+                                    let keys = match args.values_of("keys") {
+                                        Some(keys) => keys.map(|k| k.to_string()).collect(),
+                                        None => vec![]
+                                    };
+                                    operation.set_signers(keys, parse_arg(args.value_of("threshold").unwrap(), "threshold")?);
+                                    (username, operation)
+                                },
+                                _ => {
+                                    return Err(ClientError::Validation("Unrecognised proposal ! Use \"multisig propose --help\" to see available options".to_string()))
+                                }
+                            };
+
+                            client.send(client.api.propose(username, operation)?)?;
+                        },
+                        Some(("approve", args)) => {
+                            let username = args.value_of("user").unwrap().to_string();
+                            let proposal_id = parse_arg(args.value_of("id").unwrap(), "id")?;
+
+                            let receiver = lookup_arg(args, "receiver", Some(&username), client)?;
+
+                            client.send(client.api.approve(username, proposal_id, receiver)?)?;
+                        },
+                        Some(("pending", args)) => {
+                            client.pending(args.value_of("user").unwrap().to_string())?;
+                        },
+                        _ => {
+                            return Err(ClientError::Validation("Unrecognised multisig operation ! Use \"multisig --help\" to see available options".to_string()))
+                        }
+                    }
+                },
+                "name" => {
+                    match cmd.1.subcommand() {
+                        Some(("transfer", args)) => {
+                            client.send(client.api.transfer_name(
+                                args.value_of("user").unwrap().to_string(),     // required by clap
+                                args.value_of("new_key").unwrap().to_string()
+                            )?)?;
+                        },
+                        Some(("list", args)) => {
+                            client.send(client.api.list_name(
+                                args.value_of("user").unwrap().to_string(),
+                                parse_amount(args.value_of("price").unwrap(), "price")?,
+                                args.value_of("payout").unwrap().to_string()
+                            )?)?;
+                        },
+                        Some(("buy", args)) => {
+                            let username = args.value_of("user").unwrap();
+                            let listing = match (lookup_arg(args, "seller_key", Some(username), client)?, lookup_arg(args, "payout", Some(username), client)?) {
+                                (Some(seller_key), Some(payout)) => Some((seller_key, payout)),
+                                (None, None) => None,
+                                _ => {
+                                    return Err(ClientError::Validation("--seller-key and --payout go together".to_string()))
+                                }
+                            };
+
+                            client.send(client.api.buy_name(
+                                username.to_string(),
+                                args.value_of("from").unwrap().to_string(),
+                                parse_amount(args.value_of("max_price").unwrap(), "max_price")?,
+                                listing
+                            )?)?;
+                        },
+                        Some(("offer", args)) => {
+                            client.send(client.api.offer_name(
+                                args.value_of("user").unwrap().to_string(),
+                                args.value_of("from").unwrap().to_string(),
+                                parse_amount(args.value_of("price").unwrap(), "price")?
+                            )?)?;
+                        },
+                        Some(("accept", args)) => {
+                            client.send(client.api.accept_offer(
+                                args.value_of("user").unwrap().to_string(),
+                                args.value_of("buyer").unwrap().to_string(),
+                                args.value_of("payout").unwrap().to_string(),
+                                lookup_arg(args, "buyer_key", args.value_of("buyer"), client)?
+                            )?)?;
+                        },
+                        Some(("offers", args)) => {
+                            client.name_offers(args.value_of("user").unwrap().to_string())?;
+                        },
+                        _ => {
+                            return Err(ClientError::Validation("Unrecognised name operation ! Use \"name --help\" to see available options".to_string()))
+                        }
+                    }
+                },
+                "allowance" => {
+                    match cmd.1.subcommand() {
+                        Some(("grant", args)) => {
+                            client.send(client.api.approve_allowance(
+                                args.value_of("user").unwrap().to_string(),     // required by clap
+                                args.value_of("spender").unwrap().to_string(),
+                                parse_amount(args.value_of("amount").unwrap(), "amount")?
+                            )?)?;
+                        },
+                        Some(("revoke", args)) => {
+                            client.send(client.api.revoke_allowance(
+                                args.value_of("user").unwrap().to_string(),
+                                args.value_of("spender").unwrap().to_string()
+                            )?)?;
+                        },
+                        Some(("show", args)) => {
+                            client.allowances(args.value_of("user").unwrap().to_string())?;
+                        },
+                        Some(("spend", args)) => {
+                            client.send(client.api.transfer_from(
+                                args.value_of("owner").unwrap().to_string(),
+                                args.value_of("spender").unwrap().to_string(),
+                                args.value_of("receiver").unwrap().to_string(),
+                                parse_amount(args.value_of("amount").unwrap(), "amount")?
+                            )?)?;
+                        },
+                        _ => {
+                            return Err(ClientError::Validation("Unrecognised allowance operation ! Use \"allowance --help\" to see available options".to_string()))
+                        }
+                    }
+                },
+                "schedule" => {
+                    match cmd.1.subcommand() {
+                        Some(("create", args)) => {
+                            let interval = match util::parse_duration(args.value_of("interval").unwrap()) {   // required by clap
+                                Some(interval) if interval > 0 => interval,
+                                _ => {
+                                    return Err(ClientError::Validation("Invalid interval ! Use seconds, or a number followed by s, m, h or d".to_string()))
+                                }
+                            };
+
+                            client.send(client.api.create_schedule(
+                                args.value_of("user").unwrap().to_string(),
+                                args.value_of("receiver").unwrap().to_string(),
+                                parse_amount(args.value_of("amount").unwrap(), "amount")?,
+                                interval,
+                                args.value_of("runs").map_or(Ok(0), |runs| parse_arg(runs, "runs"))?,
+                                args.value_of("until").map_or(Ok(0), |until| parse_arg(until, "until"))?
+                            )?)?;
+                        },
+                        Some(("cancel", args)) => {
+                            client.send(client.api.cancel_schedule(
+                                args.value_of("user").unwrap().to_string(),
+                                parse_arg(args.value_of("id").unwrap(), "id")?
+                            )?)?;
+                        },
+                        Some(("list", args)) => {
+                            client.schedules(args.value_of("user").unwrap().to_string())?;
+                        },
+                        Some(("execute", args)) => {
+                            let receivers = lookup_arg(args, "receivers", args.value_of("user"), client)?.map(|receivers| {
+                                receivers.split(',').map(|receiver| receiver.trim().to_string()).filter(|receiver| !receiver.is_empty()).collect()
+                            });
+
+                            client.send(client.api.execute_due(args.value_of("user").unwrap().to_string(), receivers)?)?;
+                        },
+                        _ => {
+                            return Err(ClientError::Validation("Unrecognised schedule operation ! Use \"schedule --help\" to see available options".to_string()))
+                        }
+                    }
+                },
+                "escrow" => {
+                    match cmd.1.subcommand() {
+                        Some(("create", args)) => {
+                            let arbiter = args.value_of("arbiter").unwrap_or("").to_string();
+                            let module = args.value_of("module").unwrap_or("").to_string();
+                            if arbiter.is_empty() && module.is_empty() {
+                                return Err(ClientError::Validation("An escrow needs an --arbiter, a --module condition, or both".to_string()))
+                            }
+
+                            let (expires_in, plugged_for) = match (
+                                util::parse_duration(args.value_of("expires_in").unwrap()),    // required by clap
+                                util::parse_duration(args.value_of("plugged_for").unwrap_or("0"))
+                            ) {
+                                (Some(expires_in), Some(plugged_for)) => (expires_in, plugged_for),
+                                _ => {
+                                    return Err(ClientError::Validation("Invalid duration ! Use seconds, or a number followed by s, m, h or d".to_string()))
+                                }
+                            };
+
+                            client.send(client.api.create_escrow(
+                                args.value_of("user").unwrap().to_string(),
+                                args.value_of("beneficiary").unwrap().to_string(),
+                                parse_amount(args.value_of("amount").unwrap(), "amount")?,
+                                arbiter,
+                                module,
+                                plugged_for,
+                                util::get_timestamp_sec() + expires_in
+                            )?)?;
+                        },
+                        Some(("release", args)) => {
+                            client.send(client.api.settle_escrow(
+                                args.value_of("user").unwrap().to_string(),
+                                parse_arg(args.value_of("id").unwrap(), "id")?,
+                                true,
+                                lookup_arg(args, "beneficiary", args.value_of("user"), client)?
+                            )?)?;
+                        },
+                        Some(("refund", args)) => {
+                            client.send(client.api.settle_escrow(
+                                args.value_of("user").unwrap().to_string(),
+                                parse_arg(args.value_of("id").unwrap(), "id")?,
+                                false,
+                                lookup_arg(args, "beneficiary", args.value_of("user"), client)?
+                            )?)?;
+                        },
+                        Some(("list", args)) => {
+                            client.escrows(args.value_of("user").unwrap().to_string())?;
+                        },
+                        _ => {
+                            return Err(ClientError::Validation("Unrecognised escrow operation ! Use \"escrow --help\" to see available options".to_string()))
+                        }
+                    }
+                },
+                "org" => {
+                    match cmd.1.subcommand() {
+                        Some(("add", args)) => {
+                            client.send(client.api.set_member(
+                                args.value_of("org").unwrap().to_string(),     // required by clap
+                                args.value_of("key").unwrap().to_string(),
+                                args.value_of("role").unwrap().to_string(),
+                                args.value_of("budget").map_or(Ok(0.0), |budget| parse_amount(budget, "budget"))?
+                            )?)?;
+                        },
+                        Some(("remove", args)) => {
+                            client.send(client.api.remove_member(args.value_of("org").unwrap().to_string(), args.value_of("key").unwrap().to_string())?)?;
+                        },
+                        Some(("members", args)) => {
+                            client.members(args.value_of("org").unwrap().to_string())?;
+                        },
+                        Some(("plug", args)) => {
+                            client.send(client.api.plug(args.value_of("org").unwrap().to_string(), args.value_of("module").unwrap().to_string())?)?;
+                        },
+                        Some(("unplug", args)) => {
+                            client.send(client.api.unplug(args.value_of("org").unwrap().to_string(), args.value_of("module").unwrap().to_string())?)?;
+                        },
+                        _ => {
+                            return Err(ClientError::Validation("Unrecognised org operation ! Use \"org --help\" to see available options".to_string()))
+                        }
+                    }
+                },
+                "run" => {
+                    run_script(cmd.1.value_of("file").unwrap(), client, output)?;   // required by clap
+                },
+                "submit" => {
+                    client.submit(&read_batch_file(cmd.1.value_of("file").unwrap())?)?;    // required by clap
+                },
+                "inspect" => {
+                    client.inspect(&read_batch_file(cmd.1.value_of("file").unwrap())?)?;
+                },
+                "claimable" => {
+                    let username = match cmd.1.value_of("user") {
+                        Some(username) => username.to_string(),
+                        None => whoami::username()
+                    };
+
+                    client.claimable(username)?;
+                },
                 _ => {
-                    println!("Unrecognised Operation !");
-                    process::exit(1);
+                    return Err(ClientError::Validation("Unrecognised Operation !".to_string()))
                 }
             }
         },
         None => {
-            println!("No Operation specified ! Use --help to see available options");
-            process::exit(0);
+            eprintln!("No Operation specified ! Use --help to see available options");
         }
     }
 
-    /* FUTURE: Instead of try catch block, in the function that interacts with the API, call
-     * process::exit there
-     * Later, if needed create a try catch block here
-     * CURRENT: Currently simply having 'panic', if ANYTHING fails the client will end... sun ke achha to nahi lag raha bhai ye :'D
-     */
+    Ok(())
+}
+
+// Commands of a script that don't make a transaction, they'd print their output in the middle of the script's
+const NOT_SCRIPTABLE: [&str; 9] = ["list", "users", "balance", "show", "whoami", "claimable", "run", "submit", "inspect"];
+
+// {argument, option} that apply to the whole script, so they're given to run, not to its steps
+const SCRIPT_OPTIONS: [(&str, &str); 7] = [
+    ("url", "--url"), ("no_wait", "--no-wait"), ("output", "--output"), ("key", "--key"),
+    ("ephemeral", "--ephemeral"), ("offline", "--offline"), ("out", "-o")
+];
+
+fn run_script(file_path: &str, client: &OSCashierClient, output: OutputFormat) -> Result<(), ClientError> {
+    let script = script::read_script(file_path).map_err(ClientError::Validation)?;
+
+    client.start_script();
+    let mut transaction_ids: std::collections::BTreeMap<&str, String> = std::collections::BTreeMap::new();
+    let mut steps = Vec::new();
+    for step in &script.steps {
+        let command = step.args.join(" ");
+        let matches = app().try_get_matches_from(std::iter::once("os-cashier-cli").chain(step.args.iter().map(|arg| arg.as_str())))
+                           .map_err(|e| ClientError::Validation(format!("Step {} ({}): {}", step.id, command, e)))?;
+
+        if matches.subcommand_name().map_or(true, |name| NOT_SCRIPTABLE.contains(&name)) {
+            return Err(ClientError::Validation(format!("Step {} ({}): only commands that make a transaction can be scripted", step.id, command)))
+        }
+        if let Some((_, option)) = SCRIPT_OPTIONS.iter().find(|(name, _)| matches.is_present(*name)) {
+            return Err(ClientError::Validation(format!("Step {} ({}): {} applies to the whole script, pass it to run instead", step.id, command, option)))
+        }
+
+        client.set_dependencies(step.after.iter().map(|id| transaction_ids[id.as_str()].clone()).collect());
+        if let Err(e) = dispatch(&matches, client, output) {
+            eprintln!("Script stopped at step {} ({}), nothing was sent", step.id, command);
+            return Err(e)
+        }
+
+        // Each step must have added exactly one transaction
+        let ids = client.get_script_transaction_ids();
+        if ids.len() != steps.len() + 1 {
+            return Err(ClientError::Validation(format!("Step {} ({}) doesn't make a transaction", step.id, command)))
+        }
+        transaction_ids.insert(&step.id, ids[steps.len()].clone());
+        steps.push((step.id.clone(), command));
+    }
+
+    client.finish_script(&steps, script.atomic)
+}
+
+fn read_batch_file(file_path: &str) -> Result<Vec<u8>, ClientError> {
+    std::fs::read(file_path).map_err(|e| ClientError::Validation(format!("Couldn't read {}: {}", file_path, e)))
+}
+
+fn manage_keys(operation: &str, args: &ArgMatches, key: Option<&str>, output: OutputFormat) -> Result<(), ClientError> {
+    let (name, public_key, path) = match operation {
+        "generate" => {
+            let name = match args.value_of("name") {
+                Some(name) => name.to_string(),
+                None => whoami::username()
+            };
+            let privatekey = keys::random()?;
+            let path = keys::save(&name, &privatekey, args.is_present("force"), args.is_present("encrypt"))?;
+
+            (name, keys::public_key(&privatekey)?, path)
+        },
+        "import" => {
+            let name = args.value_of("name").unwrap().to_string();    // required by clap
+            let private_key = args.value_of("private_key").unwrap();
+            let privatekey = if std::path::Path::new(private_key).is_file() {
+                keys::load(std::path::Path::new(private_key))?
+            } else {
+                keys::from_hex(private_key)?
+            };
+            let path = keys::save(&name, &privatekey, args.is_present("force"), args.is_present("encrypt"))?;
+
+            (name, keys::public_key(&privatekey)?, path)
+        },
+        "show" => {
+            let path = keys::resolve(args.value_of("name").or(key))?;
+            let name = path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+
+            (name, keys::load_public_key(&path)?, path)
+        },
+        "encrypt" => {
+            let names: Vec<String> = match args.values_of("names") {
+                Some(names) => names.map(|name| name.to_string()).collect(),
+                None => vec![]
+            };
+            let encrypted = keys::encrypt_existing(&names)?;
+
+            Report::new(serde_json::json!({ "encrypted": encrypted }))
+                .table(&["Encrypted"], encrypted.iter().map(|name| vec![name.clone()]).collect())
+                .print(output);
+
+            return Ok(())
+        },
+        _ => {
+            let keys = keys::list()?;
+
+            Report::new(serde_json::json!({
+                "keys": keys.iter().map(|(name, public_key)| serde_json::json!({ "name": name, "public_key": public_key })).collect::<Vec<serde_json::Value>>()
+            }))
+            .table(&["Name", "Public key"], keys.iter().map(|(name, public_key)| vec![name.clone(), public_key.clone()]).collect())
+            .print(output);
+
+            return Ok(())
+        }
+    };
+
+    Report::new(serde_json::json!({ "name": name, "path": path.display().to_string(), "public_key": public_key }))
+        .field("Name", &name)
+        .field("Path", path.display())
+        .field("Public key", &public_key)
+        .print(output);
+
+    Ok(())
+}
+
+fn manage_mnemonic(matches: &ArgMatches, client: &OSCashierClient, output: OutputFormat) -> Result<(), ClientError> {
+    let (operation, args) = match matches.subcommand() {
+        Some(subcommand) => subcommand,
+        None => return Err(ClientError::Validation("Unrecognised mnemonic operation ! Use \"key mnemonic --help\" to see available options".to_string()))
+    };
+    let count = match args.value_of("accounts") {
+        Some(count) => Some(parse_arg::<u32>(count, "number of accounts")?),
+        None => None
+    };
+
+    let phrase = match operation {
+        "create" => {
+            let words = match args.value_of("words") {
+                Some(words) => parse_arg(words, "number of words")?,
+                None => 12
+            };
+            mnemonic::generate(words)?
+        },
+        _ => mnemonic::read()?
+    };
+
+    // (index, private key, public key, owned accounts), a new mnemonic's keys can't own any yet
+    let mut derived = Vec::new();
+    let mut derive = |index: u32| -> Result<(u32, usize), ClientError> {
+        let privatekey = mnemonic::derive(&phrase, index)?;
+        let public_key = keys::public_key(&privatekey)?;
+        let accounts = if operation == "create" { vec![] } else { client.api.get_owned_accounts(&public_key)? };
+
+        let owned = accounts.len();
+        derived.push((index, privatekey, public_key, accounts));
+        Ok((index, owned))
+    };
+
+    match count {
+        Some(count) => {
+            for index in 0..count {
+                derive(index)?;
+            }
+        },
+        None if operation == "create" => {
+            derive(0)?;
+        },
+        None => {   // scan, like wallets do, until MNEMONIC_GAP_LIMIT unused keys in a row
+            let mut unused = 0;
+            let mut index = 0;
+            while unused < MNEMONIC_GAP_LIMIT {
+                unused = if derive(index)?.1 == 0 { unused + 1 } else { 0 };
+                index += 1;
+            }
+            derived.retain(|(_, _, _, accounts)| !accounts.is_empty());
+
+            if derived.is_empty() && operation == "restore" {
+                return Err(ClientError::Validation("No key of this mnemonic owns an account, pass --accounts to restore them anyway".to_string()))
+            }
+        }
+    }
+
+    let name = match args.value_of("name") {
+        Some(name) => name.to_string(),
+        None => whoami::username()
+    };
+    let local_keys = keys::list()?;
+    let mut rows = Vec::new();
+    for (index, privatekey, public_key, accounts) in &derived {
+        let key_name = match operation {
+            "list" => local_keys.iter()
+                                .find(|(_, local_public_key)| local_public_key == public_key)
+                                .map(|(key_name, _)| key_name.clone())
+                                .unwrap_or_default(),
+            _ => {
+                let key_name = format!("{}-{}", name, index);
+                keys::save(&key_name, privatekey, args.is_present("force"), args.is_present("encrypt"))?;
+                key_name
+            }
+        };
+        rows.push((*index, key_name, public_key.clone(), accounts.clone()));
+    }
+
+    let mut report = Report::new(serde_json::json!({
+        "mnemonic": if operation == "create" { Some(phrase.to_string()) } else { None },
+        "keys": rows.iter().map(|(index, key_name, public_key, accounts)| serde_json::json!({
+            "index": index, "name": key_name, "public_key": public_key, "accounts": accounts
+        })).collect::<Vec<serde_json::Value>>()
+    }));
+    if operation == "create" {
+        eprintln!("Write the mnemonic down and keep it safe, it's the only way to restore these keys");
+        report = report.field("Mnemonic", phrase.to_string());
+    }
+
+    report.table(&["Index", "Key", "Public key", "Accounts"], rows.iter().map(|(index, key_name, public_key, accounts)| vec![
+              index.to_string(), key_name.clone(), public_key.clone(), accounts.join(",")
+          ]).collect())
+          .print(output);
+
+    Ok(())
 }
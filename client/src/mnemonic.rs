@@ -0,0 +1,146 @@
+use bip39::Mnemonic;
+use openssl::bn::{BigNum, BigNumContext};
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use rand::{thread_rng, RngCore};
+use sawtooth_sdk::signing::secp256k1::Secp256k1PrivateKey;
+
+use crate::error::ClientError;
+
+const MNEMONIC_ENV: &str = "OS_CASHIER_MNEMONIC";   // for scripts, instead of asking on the terminal
+const HARDENED: u32 = 0x8000_0000;
+const CURVE_ORDER: &str = "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEBAAEDCE6AF48A03BBFD25E8CD0364141";    // n of secp256k1
+
+/*
+ * Keys are derived with BIP-32, along m/44'/20307'/<index>', every level hardened
+ *
+ * 20307 isn't a registered SLIP-44 coin type, it only keeps these keys apart from a wallet's made from the same phrase
+ */
+const PATH_PREFIX: [u32; 2] = [44, 20307];
+
+// A new English phrase of 12, 15, 18, 21 or 24 words
+pub fn generate(words: usize) -> Result<Mnemonic, ClientError> {
+    if words % 3 != 0 || words < 12 || words > 24 {
+        return Err(ClientError::Validation("A mnemonic has 12, 15, 18, 21 or 24 words".to_string()))
+    }
+
+    let mut entropy = vec![0u8; words * 4 / 3];
+    thread_rng().fill_bytes(&mut entropy);
+
+    Mnemonic::from_entropy(&entropy).map_err(|e| ClientError::Key(format!("Couldn't create the mnemonic: {}", e)))
+}
+
+// From MNEMONIC_ENV if set, else asked on the terminal without echoing it, never as an argument (it would stay in the shell history)
+pub fn read() -> Result<Mnemonic, ClientError> {
+    let phrase = match std::env::var(MNEMONIC_ENV) {
+        Ok(phrase) => phrase,
+        Err(_) => rpassword::prompt_password_stderr("Mnemonic phrase: ")
+            .map_err(|e| ClientError::Key(format!("Couldn't read the mnemonic (or set {}): {}", MNEMONIC_ENV, e)))?
+    };
+
+    Mnemonic::parse(phrase.to_lowercase()).map_err(|e| ClientError::Validation(format!("Invalid mnemonic: {}", e)))
+}
+
+// The same phrase and index always give the same key
+pub fn derive(mnemonic: &Mnemonic, index: u32) -> Result<Secp256k1PrivateKey, ClientError> {
+    if index >= HARDENED {
+        return Err(ClientError::Validation(format!("Account index must be below {}", HARDENED)))
+    }
+
+    let path: Vec<u32> = PATH_PREFIX.iter().copied().chain(std::iter::once(index)).collect();
+    let key = derive_hardened(&mnemonic.to_seed(""), &path)?;
+
+    Secp256k1PrivateKey::from_hex(&hex::encode(key))
+        .map_err(|e| ClientError::Key(format!("Couldn't derive key {}: {}", index, e)))
+}
+
+// BIP-32 private key of `seed` at `path`, every index of it taken as hardened
+fn derive_hardened(seed: &[u8], path: &[u32]) -> Result<Vec<u8>, ClientError> {
+    let master = hmac_sha512(b"Bitcoin seed", seed)?;
+    let (mut key, mut chain_code) = (master[..32].to_vec(), master[32..].to_vec());
+
+    for child in path {
+        let mut data = vec![0u8];
+        data.extend_from_slice(&key);
+        data.extend_from_slice(&(child | HARDENED).to_be_bytes());
+
+        let derived = hmac_sha512(&chain_code, &data)?;
+        key = add_mod_order(&derived[..32], &key)?;
+        chain_code = derived[32..].to_vec();
+    }
+
+    Ok(key)
+}
+
+fn hmac_sha512(key: &[u8], data: &[u8]) -> Result<Vec<u8>, ClientError> {
+    PKey::hmac(key)
+        .and_then(|key| {
+            let mut signer = Signer::new(MessageDigest::sha512(), &key)?;
+            signer.update(data)?;
+            signer.sign_to_vec()
+        })
+        .map_err(|e| ClientError::Key(format!("Couldn't derive the key: {}", e)))
+}
+
+// (tweak + key) mod n, failing in the (about 1 in 2^127) cases BIP-32 says the index has no key
+fn add_mod_order(tweak: &[u8], key: &[u8]) -> Result<Vec<u8>, ClientError> {
+    let to_key_error = |e: openssl::error::ErrorStack| ClientError::Key(format!("Couldn't derive the key: {}", e));
+
+    let order = BigNum::from_hex_str(CURVE_ORDER).map_err(to_key_error)?;
+    let tweak = BigNum::from_slice(tweak).map_err(to_key_error)?;
+    let key = BigNum::from_slice(key).map_err(to_key_error)?;
+
+    let mut sum = BigNum::new().map_err(to_key_error)?;
+    let mut context = BigNumContext::new().map_err(to_key_error)?;
+    sum.mod_add(&tweak, &key, &order, &mut context).map_err(to_key_error)?;
+
+    if tweak >= order || sum.num_bits() == 0 {
+        return Err(ClientError::Key("This index has no valid key, use the next one".to_string()))
+    }
+
+    sum.to_vec_padded(32).map_err(to_key_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sawtooth_sdk::signing::PrivateKey;
+
+    // https://github.com/bitcoin/bips/blob/master/bip-0032.mediawiki#test-vector-1
+    #[test]
+    fn bip32_test_vector_1() {
+        let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+
+        assert_eq!(hex::encode(derive_hardened(&seed, &[]).unwrap()),
+                   "e8f32e723decf4051aefac8e2c93c9c5b214313817cdb01a1494b917c8436b35");
+        assert_eq!(hex::encode(derive_hardened(&seed, &[0]).unwrap()),
+                   "edb2e14f9ee77d26dd93b4ecede8d16ed408ce149b6cd80b0715a2d911a0afea");
+    }
+
+    // https://github.com/bitcoin/bips/blob/master/bip-0032.mediawiki#test-vector-3, keys with leading zeros
+    #[test]
+    fn bip32_test_vector_3() {
+        let seed = hex::decode("4b381541583be4423346c643850da4b320e46a87ae3d2a4e6da11eba819cd4acba45d239319ac14f863b8d5ab5a0d0c64d2e8a1e7d1457df2e5a3c51c73235be").unwrap();
+
+        assert_eq!(hex::encode(derive_hardened(&seed, &[]).unwrap()),
+                   "00ddb80b067e0d4993197fe10f2657a844a384589847602d56f0c629c81aae32");
+        assert_eq!(hex::encode(derive_hardened(&seed, &[0]).unwrap()),
+                   "491f7a2eebc7b57028e0d3faa0acda02e75c33b03c48fb288c41e2ea44e1daef");
+    }
+
+    // Users restore their keys from their phrase, these must never change
+    #[test]
+    fn phrase_gives_the_same_keys() {
+        let mnemonic = Mnemonic::parse("abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about").unwrap();
+
+        assert_eq!(derive(&mnemonic, 0).unwrap().as_hex(), "4722a6ab72508820e308d1e188dfc64d6c9414646f7da0e790fe9f86b007f94f");
+        assert_eq!(derive(&mnemonic, 1).unwrap().as_hex(), "159efe8cf297077a2cb114d3db67a43f2a544f708793857ee4469a1f5f7bc63b");
+    }
+
+    #[test]
+    fn rejects_non_hardenable_index() {
+        let mnemonic = generate(12).unwrap();
+        assert!(derive(&mnemonic, HARDENED).is_err());
+    }
+}
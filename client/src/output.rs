@@ -0,0 +1,108 @@
+use serde_json::Value;
+
+use os_cashier::ClientError;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Json,   // one JSON document on stdout, see Report
+    Table,  // for humans, the default
+    Plain   // values only, tab separated, for shell scripts
+}
+
+impl OutputFormat {
+    pub fn from_string(format: &str) -> Option<OutputFormat> {
+        match format {
+            "json" => Some(OutputFormat::Json),
+            "table" => Some(OutputFormat::Table),
+            "plain" => Some(OutputFormat::Plain),
+            _ => None
+        }
+    }
+}
+
+/*
+ * What a command prints, `json` is the stable schema, `fields` and the table are its human readable view
+ *
+ * Only the final result goes to stdout, anything else (progress, debug dumps) goes to stderr
+ */
+pub struct Report {
+    json: Value,
+    fields: Vec<(String, String)>,
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Report {
+    pub fn new(json: Value) -> Report {
+        Report {
+            json,
+            fields: Vec::new(),
+            headers: Vec::new(),
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn field(mut self, label: &str, value: impl ToString) -> Report {
+        self.fields.push((label.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn table(mut self, headers: &[&str], rows: Vec<Vec<String>>) -> Report {
+        self.headers = headers.iter().map(|header| header.to_string()).collect();
+        self.rows = rows;
+        self
+    }
+
+    pub fn print(&self, format: OutputFormat) {
+        match format {
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&self.json).unwrap_or_else(|_| self.json.to_string())),
+            OutputFormat::Plain => {
+                self.fields.iter().for_each(|(_, value)| println!("{}", value));
+                self.rows.iter().for_each(|row| println!("{}", row.join("\t")));
+            },
+            OutputFormat::Table => {
+                self.fields.iter().for_each(|(label, value)| println!("{}: {}", label, value));
+                if self.headers.is_empty() {
+                    return;
+                }
+
+                let widths: Vec<usize> = self.headers.iter().enumerate().map(|(i, header)| {
+                    self.rows.iter()
+                             .filter_map(|row| row.get(i))
+                             .map(|cell| cell.chars().count())
+                             .fold(header.chars().count(), usize::max)
+                }).collect();
+                let format_row = |row: &[String]| -> String {
+                    row.iter()
+                       .zip(widths.iter())
+                       .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                       .collect::<Vec<String>>()
+                       .join("  ")
+                       .trim_end()
+                       .to_string()
+                };
+
+                println!("{}", format_row(&self.headers));
+                println!("{}", widths.iter().map(|width| "=".repeat(*width)).collect::<Vec<String>>().join("  "));
+                self.rows.iter().for_each(|row| println!("{}", format_row(row)));
+            }
+        }
+    }
+}
+
+// Errors are part of the schema too, in json mode they go to stdout like any other result
+pub fn print_error(error: &ClientError, format: OutputFormat) {
+    match format {
+        OutputFormat::Json => {
+            let json = serde_json::json!({
+                "error": {
+                    "kind": error.kind(),
+                    "message": error.to_string(),
+                    "exit_code": error.exit_code()
+                }
+            });
+            println!("{}", serde_json::to_string_pretty(&json).unwrap_or_else(|_| json.to_string()));
+        },
+        _ => eprintln!("Error: {}", error)
+    }
+}
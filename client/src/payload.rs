@@ -1,14 +1,44 @@
 use serde_derive::{Serialize, Deserialize};
 
+use std::collections::BTreeMap;
 use std::fmt;
 
+pub const MAX_MEMO_LENGTH: usize = 256;     // bytes
+pub const MAX_RECIPIENTS: usize = 100;      // per MultiTransfer
+
 pub enum Actions {
     Register,
     PlugMod,    // add
     UnplugMod,  // remove
     // List,
     // ListMod,
-    Transfer
+    Transfer,
+    Claim,      // settle reward, keep plugged
+    Reboot,     // unplug (and settle) all, then optionally plug a new set
+    RotateKey,
+    SetRecovery,
+    SetSigners, // make it an M-of-N multisig account
+    Propose,
+    ApproveProposal,
+    Deregister, // close own account, balance goes to `receiver` or the treasury
+    Reap,       // admin only, close a dormant account
+    TransferName,   // hand the account over to `public_key`
+    ListName,   // put the account up for sale, for `amount`, proceeds to `receiver`
+    BuyName,    // buy a listed account, paying from `sponsor`
+    OfferName,  // offer `amount` from `sponsor` for an account, 0 withdraws the offer
+    AcceptOffer,    // sell to the offer from `sponsor`, proceeds to `receiver`
+    Approve,    // let `spender` move up to `amount` out of this account
+    Revoke,
+    TransferFrom,   // `spender` moving `amount` from `name` to `receiver`
+    MultiTransfer,  // one debit from `name`, credits to each of `recipients`
+    CreateSchedule, // pay `amount` to `receiver` every `interval` seconds
+    CancelSchedule,
+    ExecuteDue,     // anyone may run the due schedules of `name`
+    CreateEscrow,   // lock `amount` of `name` for `receiver`, released by `arbiter` or once `module` has been plugged `min_plugged` seconds
+    ReleaseEscrow,
+    RefundEscrow,
+    SetMember,      // give `public_key` a `role` in the organisation `name`, members get `amount` as plug budget
+    RemoveMember
 }
 
 impl Actions {
@@ -19,6 +49,32 @@ impl Actions {
             "PlugMod" => Some(Actions::PlugMod),
             "UnplugMod" => Some(Actions::UnplugMod),
             "Transfer" => Some(Actions::Transfer),
+            "Claim" => Some(Actions::Claim),
+            "Reboot" => Some(Actions::Reboot),
+            "RotateKey" => Some(Actions::RotateKey),
+            "SetRecovery" => Some(Actions::SetRecovery),
+            "SetSigners" => Some(Actions::SetSigners),
+            "Propose" => Some(Actions::Propose),
+            "ApproveProposal" => Some(Actions::ApproveProposal),
+            "Deregister" => Some(Actions::Deregister),
+            "Reap" => Some(Actions::Reap),
+            "TransferName" => Some(Actions::TransferName),
+            "ListName" => Some(Actions::ListName),
+            "BuyName" => Some(Actions::BuyName),
+            "OfferName" => Some(Actions::OfferName),
+            "AcceptOffer" => Some(Actions::AcceptOffer),
+            "Approve" => Some(Actions::Approve),
+            "Revoke" => Some(Actions::Revoke),
+            "TransferFrom" => Some(Actions::TransferFrom),
+            "MultiTransfer" => Some(Actions::MultiTransfer),
+            "CreateSchedule" => Some(Actions::CreateSchedule),
+            "CancelSchedule" => Some(Actions::CancelSchedule),
+            "ExecuteDue" => Some(Actions::ExecuteDue),
+            "CreateEscrow" => Some(Actions::CreateEscrow),
+            "ReleaseEscrow" => Some(Actions::ReleaseEscrow),
+            "RefundEscrow" => Some(Actions::RefundEscrow),
+            "SetMember" => Some(Actions::SetMember),
+            "RemoveMember" => Some(Actions::RemoveMember),
             _ => None
         }
     }
@@ -30,7 +86,33 @@ impl ToString for Actions {
             Actions::Register => "Register",
             Actions::PlugMod => "PlugMod",
             Actions::UnplugMod => "UnplugMod",
-            Actions::Transfer => "Transfer"
+            Actions::Transfer => "Transfer",
+            Actions::Claim => "Claim",
+            Actions::Reboot => "Reboot",
+            Actions::RotateKey => "RotateKey",
+            Actions::SetRecovery => "SetRecovery",
+            Actions::SetSigners => "SetSigners",
+            Actions::Propose => "Propose",
+            Actions::ApproveProposal => "ApproveProposal",
+            Actions::Deregister => "Deregister",
+            Actions::Reap => "Reap",
+            Actions::TransferName => "TransferName",
+            Actions::ListName => "ListName",
+            Actions::BuyName => "BuyName",
+            Actions::OfferName => "OfferName",
+            Actions::AcceptOffer => "AcceptOffer",
+            Actions::Approve => "Approve",
+            Actions::Revoke => "Revoke",
+            Actions::TransferFrom => "TransferFrom",
+            Actions::MultiTransfer => "MultiTransfer",
+            Actions::CreateSchedule => "CreateSchedule",
+            Actions::CancelSchedule => "CancelSchedule",
+            Actions::ExecuteDue => "ExecuteDue",
+            Actions::CreateEscrow => "CreateEscrow",
+            Actions::ReleaseEscrow => "ReleaseEscrow",
+            Actions::RefundEscrow => "RefundEscrow",
+            Actions::SetMember => "SetMember",
+            Actions::RemoveMember => "RemoveMember"
         }.to_string()
     }
 }
@@ -41,13 +123,57 @@ impl fmt::Debug for Actions {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OSCashierPayload {
     action: String,
     name: String,
     receiver: String,
     amount: f32,
-    module: String
+    module: String,
+    #[serde(default)]
+    modules: Vec<String>,   // module set to plug after a Reboot
+    #[serde(default)]
+    public_key: String,     // new key for RotateKey, recovery key for SetRecovery
+    #[serde(default)]
+    timelock: u64,          // seconds
+    #[serde(default)]
+    keys: Vec<String>,      // multisig signers
+    #[serde(default)]
+    threshold: u32,         // approvals needed out of `keys`
+    #[serde(default)]
+    proposal_id: u64,
+    #[serde(default)]
+    operation: Option<Box<OSCashierPayload>>,   // the operation a Propose wants to run
+    #[serde(default)]
+    sponsor: String,        // existing account vouching for a Register, or paying for a Register/BuyName/OfferName
+    #[serde(default)]
+    pow_nonce: u64,         // registration puzzle solution
+    #[serde(default)]
+    spender: String,        // account allowed to spend from `name`
+    #[serde(default)]
+    memo: String,           // free text note on a transfer
+    #[serde(default)]
+    idempotency_key: String,    // a repeated key (per payer, within a window) is rejected, so retries can't double-pay
+    #[serde(default)]
+    recipients: BTreeMap<String,f32>,   // {receiver, amount} for a MultiTransfer
+    #[serde(default)]
+    interval: u64,          // seconds between scheduled payments
+    #[serde(default)]
+    runs: u32,              // number of scheduled payments, 0 for no limit
+    #[serde(default)]
+    end_at: u64,            // timestamp after which a schedule stops, 0 for never
+    #[serde(default)]
+    schedule_id: u64,
+    #[serde(default)]
+    arbiter: String,        // public key that may release or refund an escrow
+    #[serde(default)]
+    min_plugged: u64,       // escrow condition, seconds `module` must be plugged by the beneficiary
+    #[serde(default)]
+    deadline: u64,          // timestamp after which an escrow can be refunded
+    #[serde(default)]
+    escrow_id: u64,
+    #[serde(default)]
+    role: String            // admin, member or viewer
 }
 
 impl OSCashierPayload {
@@ -57,7 +183,29 @@ impl OSCashierPayload {
             name: username,
             receiver: String::from(""),
             amount: 0.0,
-            module: String::from("")
+            module: String::from(""),
+            modules: Vec::new(),
+            public_key: String::from(""),
+            timelock: 0,
+            keys: Vec::new(),
+            threshold: 0,
+            proposal_id: 0,
+            operation: None,
+            sponsor: String::from(""),
+            pow_nonce: 0,
+            spender: String::from(""),
+            memo: String::from(""),
+            idempotency_key: String::from(""),
+            recipients: BTreeMap::new(),
+            interval: 0,
+            runs: 0,
+            end_at: 0,
+            schedule_id: 0,
+            arbiter: String::from(""),
+            min_plugged: 0,
+            deadline: 0,
+            escrow_id: 0,
+            role: String::from("")
         }
     }
 
@@ -76,6 +224,79 @@ impl OSCashierPayload {
     pub fn set_module(&mut self, module_name: String) {
         self.module = module_name;
     }
+
+    pub fn set_modules(&mut self, module_names: Vec<String>) {
+        self.modules = module_names;
+    }
+
+    pub fn set_public_key(&mut self, public_key: String) {
+        self.public_key = public_key;
+    }
+
+    pub fn set_timelock(&mut self, timelock: u64) {
+        self.timelock = timelock;
+    }
+
+    pub fn set_signers(&mut self, keys: Vec<String>, threshold: u32) {
+        self.keys = keys;
+        self.threshold = threshold;
+    }
+
+    pub fn set_proposal_id(&mut self, proposal_id: u64) {
+        self.proposal_id = proposal_id;
+    }
+
+    pub fn set_operation(&mut self, operation: OSCashierPayload) {
+        self.operation = Some(Box::new(operation));
+    }
+
+    pub fn set_sponsor(&mut self, sponsor: String) {
+        self.sponsor = sponsor;
+    }
+
+    pub fn set_pow_nonce(&mut self, pow_nonce: u64) {
+        self.pow_nonce = pow_nonce;
+    }
+
+    pub fn set_spender(&mut self, spender: String) {
+        self.spender = spender;
+    }
+
+    pub fn set_memo(&mut self, memo: String) {
+        self.memo = memo;
+    }
+
+    pub fn set_idempotency_key(&mut self, idempotency_key: String) {
+        self.idempotency_key = idempotency_key;
+    }
+
+    pub fn set_recipients(&mut self, recipients: BTreeMap<String,f32>) {
+        self.recipients = recipients;
+    }
+
+    pub fn set_schedule(&mut self, interval: u64, runs: u32, end_at: u64) {
+        self.interval = interval;
+        self.runs = runs;
+        self.end_at = end_at;
+    }
+
+    pub fn set_schedule_id(&mut self, schedule_id: u64) {
+        self.schedule_id = schedule_id;
+    }
+
+    pub fn set_escrow_terms(&mut self, arbiter: String, min_plugged: u64, deadline: u64) {
+        self.arbiter = arbiter;
+        self.min_plugged = min_plugged;
+        self.deadline = deadline;
+    }
+
+    pub fn set_escrow_id(&mut self, escrow_id: u64) {
+        self.escrow_id = escrow_id;
+    }
+
+    pub fn set_role(&mut self, role: String) {
+        self.role = role;
+    }
 }
 
 // getters meant to be used by processor only
@@ -85,6 +306,11 @@ impl OSCashierPayload {
         Actions::from_string(&self.action)
     }
 
+    // raw action string, for messages about unsupported actions
+    pub fn get_action_name(&self) -> String {
+        self.action.clone()
+    }
+
     pub fn get_name(&self) -> String {
         self.name.clone()
     }
@@ -100,4 +326,92 @@ impl OSCashierPayload {
     pub fn get_module_name(&self) -> String {
         self.module.clone()
     }
+
+    pub fn get_modules(&self) -> Vec<String> {
+        self.modules.clone()
+    }
+
+    pub fn get_public_key(&self) -> String {
+        self.public_key.clone()
+    }
+
+    pub fn get_timelock(&self) -> u64 {
+        self.timelock
+    }
+
+    pub fn get_keys(&self) -> Vec<String> {
+        self.keys.clone()
+    }
+
+    pub fn get_threshold(&self) -> u32 {
+        self.threshold
+    }
+
+    pub fn get_proposal_id(&self) -> u64 {
+        self.proposal_id
+    }
+
+    pub fn get_operation(&self) -> Option<&OSCashierPayload> {
+        self.operation.as_deref()
+    }
+
+    pub fn get_sponsor(&self) -> String {
+        self.sponsor.clone()
+    }
+
+    pub fn get_pow_nonce(&self) -> u64 {
+        self.pow_nonce
+    }
+
+    pub fn get_spender(&self) -> String {
+        self.spender.clone()
+    }
+
+    pub fn get_memo(&self) -> String {
+        self.memo.clone()
+    }
+
+    pub fn get_idempotency_key(&self) -> String {
+        self.idempotency_key.clone()
+    }
+
+    pub fn get_recipients(&self) -> BTreeMap<String,f32> {
+        self.recipients.clone()
+    }
+
+    pub fn get_interval(&self) -> u64 {
+        self.interval
+    }
+
+    pub fn get_runs(&self) -> u32 {
+        self.runs
+    }
+
+    pub fn get_end_at(&self) -> u64 {
+        self.end_at
+    }
+
+    pub fn get_schedule_id(&self) -> u64 {
+        self.schedule_id
+    }
+
+    pub fn get_arbiter(&self) -> String {
+        self.arbiter.clone()
+    }
+
+    pub fn get_min_plugged(&self) -> u64 {
+        self.min_plugged
+    }
+
+    pub fn get_deadline(&self) -> u64 {
+        self.deadline
+    }
+
+    pub fn get_escrow_id(&self) -> u64 {
+        self.escrow_id
+    }
+
+    pub fn get_role(&self) -> String {
+        self.role.clone()
+    }
 }
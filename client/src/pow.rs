@@ -0,0 +1,63 @@
+// Registration puzzle, the hash covers the name and its owner key, so a solution can't be reused for another
+pub fn leading_zero_bits(username: &str, publickey: &str, nonce: u64) -> u32 {
+    let hash = openssl::sha::sha256(format!("{}:{}:{}", username, publickey, nonce).as_bytes());
+
+    let mut bits = 0;
+    for byte in hash.iter() {
+        bits += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    bits
+}
+
+pub fn is_solved(username: &str, publickey: &str, nonce: u64, difficulty: u32) -> bool {
+    leading_zero_bits(username, publickey, nonce) >= difficulty
+}
+
+// Expected 2^difficulty hashes, only the client ever calls this
+#[allow(unused)]
+pub fn solve(username: &str, publickey: &str, difficulty: u32) -> u64 {
+    (0u64..).find(|nonce| is_solved(username, publickey, *nonce, difficulty))
+            .expect("Exhausted all nonces")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "02a1633cafcc01ebfb6d78e39f687a1f0995c62fc95f51ead10a02ee0be551b5dc";
+
+    #[test]
+    fn no_difficulty_is_always_solved() {
+        assert!(is_solved("alice", KEY, 0, 0));
+        assert_eq!(solve("alice", KEY, 0), 0);
+    }
+
+    #[test]
+    fn solution_meets_the_difficulty() {
+        let nonce = solve("alice", KEY, 10);
+        assert!(leading_zero_bits("alice", KEY, nonce) >= 10);
+        assert!(is_solved("alice", KEY, nonce, 10));
+
+        // The first solution, so every smaller nonce falls short
+        assert!((0..nonce).all(|nonce| !is_solved("alice", KEY, nonce, 10)));
+    }
+
+    #[test]
+    fn difficulty_is_a_lower_bound() {
+        let bits = leading_zero_bits("alice", KEY, 42);
+        assert!(is_solved("alice", KEY, 42, bits));
+        assert!(!is_solved("alice", KEY, 42, bits + 1));
+    }
+
+    #[test]
+    fn solution_is_bound_to_name_and_key() {
+        // A change here would invalidate every solution in flight, the processor must agree with the client
+        assert_eq!(solve("alice", KEY, 10), 248);
+
+        assert!(!is_solved("bob", KEY, 248, 10));
+        assert!(!is_solved("alice", &format!("03{}", &KEY[2..]), 248, 10));
+    }
+}
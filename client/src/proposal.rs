@@ -0,0 +1,41 @@
+use std::collections::BTreeSet;
+use serde_derive::{Serialize, Deserialize};
+
+// `super` is the crate root in the client and `structs` in the processor, both have a `payload` module
+use super::payload::OSCashierPayload;
+
+// An operation on a multisig account, waiting till enough of its signers approve it
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Proposal {
+    operation: OSCashierPayload,
+    approvals: BTreeSet<String>,    // public keys of the signers that approved
+    created_at: u64     // block timestamp, 0 if there was no block info on chain
+}
+
+#[allow(unused)]
+impl Proposal {
+    pub fn new(operation: OSCashierPayload, created_at: u64) -> Proposal {
+        Proposal {
+            operation,
+            approvals: BTreeSet::new(),
+            created_at
+        }
+    }
+
+    pub fn get_operation(&self) -> &OSCashierPayload {
+        &self.operation
+    }
+
+    pub fn get_approvals(&self) -> &BTreeSet<String> {
+        &self.approvals
+    }
+
+    pub fn get_created_at(&self) -> u64 {
+        self.created_at
+    }
+
+    // false if this key had already approved
+    pub fn approve(&mut self, publickey: String) -> bool {
+        self.approvals.insert(publickey)
+    }
+}
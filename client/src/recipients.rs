@@ -0,0 +1,73 @@
+use std::collections::BTreeMap;
+use std::{fs, path};
+
+/*
+ * Reads {receiver, amount} for a multi transfer, a receiver listed more than once gets the sum
+ *
+ * `.json` files are either {"bob": 2.5, ...} or [{"receiver": "bob", "amount": 2.5}, ...]
+ * anything else is read as CSV, "receiver,amount" per line, a header line and '#' comments are skipped
+ */
+pub fn read_recipients(file_path: &str) -> Result<BTreeMap<String,f32>, String> {
+    let contents = match fs::read_to_string(file_path) {
+        Ok(contents) => contents,
+        Err(e) => return Err(format!("Couldn't read {}: {}", file_path, e))
+    };
+
+    let is_json = path::Path::new(file_path)
+                      .extension()
+                      .map_or(false, |extension| extension == "json");
+
+    let entries = if is_json { parse_json(&contents)? } else { parse_csv(&contents)? };
+
+    let mut recipients = BTreeMap::new();
+    for (receiver, amount) in entries {
+        *recipients.entry(receiver).or_insert(0.0) += amount;
+    }
+    Ok(recipients)
+}
+
+fn parse_json(contents: &str) -> Result<Vec<(String,f32)>, String> {
+    let json: serde_json::Value = match serde_json::from_str(contents) {
+        Ok(json) => json,
+        Err(e) => return Err(format!("Invalid JSON: {}", e))
+    };
+
+    let to_amount = |receiver: &str, amount: &serde_json::Value| match amount.as_f64() {
+        Some(amount) => Ok((receiver.to_string(), amount as f32)),
+        None => Err(format!("Amount for {} is not a number", receiver))
+    };
+
+    match json {
+        serde_json::Value::Object(map) => map.iter().map(|(receiver, amount)| to_amount(receiver.as_str(), amount)).collect(),
+        serde_json::Value::Array(entries) => entries.iter().map(|entry| {
+            match entry["receiver"].as_str() {
+                Some(receiver) => to_amount(receiver, &entry["amount"]),
+                None => Err(format!("Entry without a \"receiver\": {}", entry))
+            }
+        }).collect(),
+        _ => Err("Expected a JSON object or array".to_string())
+    }
+}
+
+fn parse_csv(contents: &str) -> Result<Vec<(String,f32)>, String> {
+    let mut entries = Vec::new();
+
+    for (line_number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut fields = line.splitn(2, ',').map(|field| field.trim());
+        let receiver = fields.next().unwrap_or("");
+        let amount = fields.next().unwrap_or("");
+
+        match amount.parse::<f32>() {
+            Ok(amount) if !receiver.is_empty() => entries.push((receiver.to_string(), amount)),
+            _ if line_number == 0 => continue,     // header
+            _ => return Err(format!("Line {}: expected \"receiver,amount\", got \"{}\"", line_number + 1, line))
+        }
+    }
+
+    Ok(entries)
+}
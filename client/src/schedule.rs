@@ -0,0 +1,120 @@
+use serde_derive::{Serialize, Deserialize};
+
+// A standing order, paying `amount` to `receiver` every `interval` seconds, times are chain (block) timestamps
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Schedule {
+    receiver: String,
+    amount: f32,
+    interval: u64,
+    next_due: u64,
+    runs_left: Option<u32>, // None -> no limit
+    end_at: Option<u64>     // no payments fall due after this
+}
+
+#[allow(unused)]
+impl Schedule {
+    pub fn new(receiver: String, amount: f32, interval: u64, first_due: u64, runs_left: Option<u32>, end_at: Option<u64>) -> Schedule {
+        Schedule {
+            receiver,
+            amount,
+            interval,
+            next_due: first_due,
+            runs_left,
+            end_at
+        }
+    }
+
+    pub fn get_receiver(&self) -> String {
+        self.receiver.clone()
+    }
+
+    pub fn get_amount(&self) -> f32 {
+        self.amount
+    }
+
+    pub fn get_interval(&self) -> u64 {
+        self.interval
+    }
+
+    pub fn get_next_due(&self) -> u64 {
+        self.next_due
+    }
+
+    pub fn get_runs_left(&self) -> Option<u32> {
+        self.runs_left
+    }
+
+    pub fn get_end_at(&self) -> Option<u64> {
+        self.end_at
+    }
+
+    pub fn is_due(&self, now: u64) -> bool {
+        !self.is_finished() && now >= self.next_due
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.runs_left == Some(0) || self.end_at.map_or(false, |end_at| self.next_due > end_at)
+    }
+
+    // After a payment, one payment per interval, so missed intervals are caught up by executing again
+    pub fn advance(&mut self) {
+        self.next_due += self.interval;
+        self.runs_left = self.runs_left.map(|runs| runs.saturating_sub(1));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn due_from_the_first_time_on() {
+        let schedule = Schedule::new("bob".to_string(), 5.0, 60, 1000, None, None);
+        assert!(!schedule.is_due(999));
+        assert!(schedule.is_due(1000));
+        assert!(schedule.is_due(5000));
+    }
+
+    #[test]
+    fn advance_moves_one_interval() {
+        let mut schedule = Schedule::new("bob".to_string(), 5.0, 60, 1000, None, None);
+        schedule.advance();
+        assert_eq!(schedule.get_next_due(), 1060);
+        assert_eq!(schedule.get_runs_left(), None);
+
+        // Missed intervals stay due till they are caught up
+        assert!(schedule.is_due(1200));
+        schedule.advance();
+        schedule.advance();
+        assert!(schedule.is_due(1200));     // 1180
+        schedule.advance();
+        assert!(!schedule.is_due(1200));    // 1240
+        assert!(!schedule.is_finished());
+    }
+
+    #[test]
+    fn finishes_after_its_runs() {
+        let mut schedule = Schedule::new("bob".to_string(), 5.0, 60, 1000, Some(2), None);
+        schedule.advance();
+        assert_eq!(schedule.get_runs_left(), Some(1));
+        assert!(!schedule.is_finished());
+
+        schedule.advance();
+        assert!(schedule.is_finished());
+        assert!(!schedule.is_due(u64::MAX));
+
+        schedule.advance();
+        assert_eq!(schedule.get_runs_left(), Some(0));
+    }
+
+    #[test]
+    fn finishes_after_its_end() {
+        let mut schedule = Schedule::new("bob".to_string(), 5.0, 60, 1000, None, Some(1060));
+        schedule.advance();
+        assert!(!schedule.is_finished());   // a payment due exactly at the end still counts
+
+        schedule.advance();
+        assert!(schedule.is_finished());
+        assert!(!schedule.is_due(u64::MAX));
+    }
+}
@@ -0,0 +1,106 @@
+use std::collections::BTreeSet;
+use std::fs;
+
+use serde_derive::Deserialize;
+
+const MAX_SCRIPT_STEPS: usize = 100;
+
+/*
+ * A script for "run", the commands are the same as on the command line, without the program name
+ *
+ *     atomic: true                 # the default, all steps in one batch, applied all together or not at all
+ *     steps:
+ *       - register alice
+ *       - [transfer, alice, bob, "5", --memo, "rent for may"]   # a list when an argument has spaces
+ *       - id: plug                 # steps can be named, else they're "1", "2", ...
+ *         command: plug alice slab_allocator
+ *         after: ["1"]             # with atomic: false, steps that must be committed first
+ */
+#[derive(Deserialize)]
+struct ScriptFile {
+    #[serde(default = "default_atomic")]
+    atomic: bool,
+    steps: Vec<StepEntry>,
+}
+
+fn default_atomic() -> bool {
+    true
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StepEntry {
+    Command(Command),
+    Named {
+        id: Option<String>,
+        command: Command,
+        #[serde(default)]
+        after: Vec<String>,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Command {
+    Line(String),
+    Args(Vec<String>),
+}
+
+pub struct Step {
+    pub id: String,
+    pub args: Vec<String>,
+    pub after: Vec<String>,     // ids of earlier steps
+}
+
+pub struct Script {
+    pub atomic: bool,
+    pub steps: Vec<Step>,
+}
+
+pub fn read_script(file_path: &str) -> Result<Script, String> {
+    let contents = match fs::read_to_string(file_path) {
+        Ok(contents) => contents,
+        Err(e) => return Err(format!("Couldn't read {}: {}", file_path, e))
+    };
+
+    let script: ScriptFile = match serde_yaml::from_str(&contents) {
+        Ok(script) => script,
+        Err(e) => return Err(format!("Invalid script {}: {}", file_path, e))
+    };
+
+    if script.steps.is_empty() || script.steps.len() > MAX_SCRIPT_STEPS {
+        return Err(format!("A script has 1 to {} steps", MAX_SCRIPT_STEPS))
+    }
+
+    let mut steps: Vec<Step> = Vec::new();
+    let mut ids = BTreeSet::new();
+    for (i, entry) in script.steps.into_iter().enumerate() {
+        let (id, command, after) = match entry {
+            StepEntry::Command(command) => (None, command, vec![]),
+            StepEntry::Named { id, command, after } => (id, command, after)
+        };
+        let id = id.unwrap_or_else(|| (i + 1).to_string());
+
+        let args: Vec<String> = match command {
+            Command::Line(line) => line.split_whitespace().map(|arg| arg.to_string()).collect(),
+            Command::Args(args) => args
+        };
+        if args.is_empty() {
+            return Err(format!("Step {} has no command", id))
+        }
+
+        if !after.is_empty() && script.atomic {
+            return Err(format!("Step {}: \"after\" needs \"atomic: false\", an atomic script is applied all at once", id))
+        }
+        if let Some(unknown) = after.iter().find(|dependency| !ids.contains(*dependency)) {
+            return Err(format!("Step {}: \"{}\" isn't an earlier step", id, unknown))
+        }
+        if !ids.insert(id.clone()) {
+            return Err(format!("Step id \"{}\" is used twice", id))
+        }
+
+        steps.push(Step { id, args, after });
+    }
+
+    Ok(Script { atomic: script.atomic, steps })
+}
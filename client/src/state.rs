@@ -0,0 +1,67 @@
+use std::collections::{BTreeMap, BTreeSet};
+use serde_derive::Deserialize;
+
+use crate::proposal::Proposal;
+use crate::schedule::Schedule;
+use crate::escrow::Escrow;
+
+// Client side view of the processor's `_InternalOSCashierState`, only the fields we display
+// Unknown fields are ignored by serde, so this keeps decoding as the processor state grows
+#[allow(unused)]
+#[derive(Debug, Deserialize)]
+pub struct AccountState {
+    pub name: String,
+    pub key: String,    // public key
+    pub points: f64,
+    pub mods: BTreeMap<String,u64>, // {str, timepoint}, unix timestamp in seconds
+    #[serde(default)]
+    pub signers: Vec<String>,
+    #[serde(default)]
+    pub threshold: u32,
+    #[serde(default)]
+    pub proposals: BTreeMap<u64,Proposal>,
+    #[serde(default)]
+    pub listing: Option<Listing>,
+    #[serde(default)]
+    pub offers: BTreeMap<String,f32>,
+    #[serde(default)]
+    pub allowances: BTreeMap<String,f32>,
+    #[serde(default)]
+    pub schedules: BTreeMap<u64,Schedule>,
+    #[serde(default)]
+    pub escrows: BTreeMap<u64,Escrow>,
+    #[serde(default)]
+    pub members: BTreeMap<String,OrgMember>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Listing {
+    pub price: f32,
+    pub payout: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OrgMember {
+    pub role: String,   // Admin, Member or Viewer
+    pub budget: f32,
+}
+
+impl AccountState {
+    pub fn from_bytes( state_bytes: &[u8] ) -> Result<AccountState, serde_cbor::Error> {
+        serde_cbor::from_slice( state_bytes )
+    }
+}
+
+// Reverse index entry, the accounts owned by a public key
+#[allow(unused)]
+#[derive(Debug, Deserialize)]
+pub struct KeyIndex {
+    pub key: String,
+    pub accounts: BTreeSet<String>,
+}
+
+impl KeyIndex {
+    pub fn from_bytes( index_bytes: &[u8] ) -> Result<KeyIndex, serde_cbor::Error> {
+        serde_cbor::from_slice( index_bytes )
+    }
+}
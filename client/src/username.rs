@@ -0,0 +1,79 @@
+use unicode_normalization::UnicodeNormalization;
+
+pub const MIN_LENGTH: usize = 3;
+pub const MAX_LENGTH: usize = 32;
+
+// System accounts, and names that could pass for them
+pub const RESERVED_NAMES: [&str; 8] = [
+    "os", "root", "admin", "system", "treasury", "kernel", "cashier", "sawtooth"
+];
+
+// NFKC folds look-alikes such as fullwidth letters and ligatures into their plain form
+pub fn normalize(name: &str) -> String {
+    name.nfkc().collect::<String>().to_lowercase()
+}
+
+// Only canonical names are accepted, so two different-looking names can't be the same person
+pub fn validate(name: &str) -> Result<(),String> {
+    let normalized = normalize(name);
+    if normalized != name {
+        return Err(format!("Username \"{}\" is not in canonical form, use \"{}\"", name, normalized))
+    }
+
+    if name.len() < MIN_LENGTH || name.len() > MAX_LENGTH {
+        return Err(format!("Username must be {} to {} characters long", MIN_LENGTH, MAX_LENGTH))
+    }
+
+    if !name.starts_with(|c: char| c.is_ascii_lowercase()) {
+        return Err("Username must start with a letter (a-z)".to_string())
+    }
+
+    if !name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-') {
+        return Err("Username can only have a-z, 0-9, '_' and '-'".to_string())
+    }
+
+    if RESERVED_NAMES.contains(&name) {
+        return Err(format!("Username \"{}\" is reserved", name))
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_plain_names() {
+        assert_eq!(validate("alice"), Ok(()));
+        assert_eq!(validate("bob_2-x"), Ok(()));
+        assert_eq!(validate(&"a".repeat(MAX_LENGTH)), Ok(()));
+    }
+
+    #[test]
+    fn rejects_names_that_arent_canonical() {
+        assert!(validate("Alice").is_err());
+        assert!(validate("ａlice").is_err());  // fullwidth a
+        assert!(validate("ﬁsh").is_err());     // fi ligature
+
+        assert_eq!(normalize("Ａlice"), "alice");
+        assert_eq!(normalize("ﬁsh"), "fish");
+    }
+
+    #[test]
+    fn rejects_bad_lengths_and_characters() {
+        assert!(validate("ab").is_err());
+        assert!(validate(&"a".repeat(MAX_LENGTH + 1)).is_err());
+        assert!(validate("1alice").is_err());
+        assert!(validate("_alice").is_err());
+        assert!(validate("al.ice").is_err());
+        assert!(validate("al ice").is_err());
+    }
+
+    #[test]
+    fn rejects_reserved_names() {
+        for name in RESERVED_NAMES.iter().filter(|name| name.len() >= MIN_LENGTH) {
+            assert_eq!(validate(name), Err(format!("Username \"{}\" is reserved", name)));
+        }
+    }
+}
@@ -2,3 +2,44 @@
 // pub fn bytes_to_hex_string(bytes: &[u8]) -> String {
 //     hex::encode(bytes)
 // }
+
+pub fn get_timestamp_sec() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// "90", "90s", "15m", "2h" or "1d" to seconds
+pub fn parse_duration(duration: &str) -> Option<u64> {
+    let duration = duration.trim();
+    let (number, multiplier) = match duration.chars().last()? {
+        's' => (&duration[..duration.len() - 1], 1),
+        'm' => (&duration[..duration.len() - 1], 60),
+        'h' => (&duration[..duration.len() - 1], 60 * 60),
+        'd' => (&duration[..duration.len() - 1], 24 * 60 * 60),
+        _ => (duration, 1)
+    };
+
+    number.parse::<u64>().ok().and_then(|number| number.checked_mul(multiplier))
+}
+
+// 93784 -> "1d 2h 3m 4s"
+pub fn format_duration(seconds: u64) -> String {
+    let units = [(24 * 60 * 60, "d"), (60 * 60, "h"), (60, "m"), (1, "s")];
+
+    let mut remaining = seconds;
+    let mut parts = Vec::new();
+    for (unit_seconds, suffix) in units.iter() {
+        if remaining >= *unit_seconds {
+            parts.push(format!("{}{}", remaining / unit_seconds, suffix));
+            remaining %= unit_seconds;
+        }
+    }
+
+    if parts.is_empty() {
+        "0s".to_string()
+    } else {
+        parts.join(" ")
+    }
+}
@@ -1,8 +1,12 @@
 use sawtooth_sdk::messages::processor::TpProcessRequest;
 use sawtooth_sdk::processor::handler::{ApplyError, TransactionContext, TransactionHandler};
 
-use crate::payload_impl::{OSCashierPayload,Actions};
-use crate::structs::state::OSCashierState;
+use crate::payload_impl::{OSCashierPayload,Actions,MAX_MEMO_LENGTH,MAX_RECIPIENTS};
+use crate::structs::state::{OSCashierState, _InternalOSCashierState, Listing, OrgRole, TREASURY_NAME, DEFAULT_INIT_POINTS};
+use crate::structs::schedule::Schedule;
+use crate::structs::escrow::{Escrow, EscrowCondition};
+use crate::username;
+use crate::pow;
 
 pub struct OSCashierHandler {
    family_name: String,
@@ -12,6 +16,18 @@ pub struct OSCashierHandler {
 
 const FAMILY_NAME: &str = "os-cashier";
 
+// On-chain settings read by the processor, set through the sawtooth settings family
+const SETTING_ADMIN_KEYS: &str = "os_cashier.admin_keys";          // comma separated public keys
+const SETTING_DORMANT_PERIOD: &str = "os_cashier.dormant_period";  // seconds without activity before an account can be reaped
+const SETTING_REGISTRATION_MODE: &str = "os_cashier.registration_mode";  // comma separated, any of open,pow,sponsor,fee
+const SETTING_REGISTRATION_FEE: &str = "os_cashier.registration_fee";
+const SETTING_POW_DIFFICULTY: &str = "os_cashier.pow_difficulty";  // leading zero bits
+const SETTING_FAUCET_AMOUNT: &str = "os_cashier.faucet_amount";    // points a new account starts with
+const SETTING_IDEMPOTENCY_WINDOW: &str = "os_cashier.idempotency_window";  // seconds an idempotency key is remembered
+
+const DEFAULT_POW_DIFFICULTY: u32 = 20;
+const DEFAULT_IDEMPOTENCY_WINDOW: u64 = 24 * 60 * 60;
+
 impl OSCashierHandler {
     pub fn new() -> OSCashierHandler {
         OSCashierHandler {
@@ -26,11 +42,296 @@ impl OSCashierHandler {
             openssl::sha::sha512(FAMILY_NAME.as_bytes())
         )[0..6].to_string() // return first 6 chars in the string
     }
+
+    // Sawtooth signer keys are compressed secp256k1 public keys, ie. 33 bytes hex encoded
+    fn is_valid_public_key(key: &str) -> bool {
+        key.len() == 66 && hex::decode(key).is_ok()
+    }
+
+    // Operations on multisig accounts have to go through Propose/Approve
+    fn reject_if_multisig(internal_state: &_InternalOSCashierState) -> Result<(),ApplyError> {
+        if internal_state.is_multisig() {
+            return Err(ApplyError::InvalidTransaction(format!(
+                "{} is a multisig account, Propose this operation instead", internal_state.get_name()
+            )))
+        }
+        Ok(())
+    }
+
+    /*
+     * On an organisation, only the owner, admins and members (spending `cost` of their budget) may operate modules
+     *
+     * Other accounts are left as they are
+     */
+    fn check_org_operator(internal_state: &mut _InternalOSCashierState, signerkey: &str, cost: f32) -> Result<(),ApplyError> {
+        if !internal_state.is_org() || internal_state.get_key() == signerkey {
+            return Ok(())
+        }
+
+        match internal_state.get_member_role(signerkey) {
+            Some(OrgRole::Admin) => Ok(()),
+            Some(OrgRole::Member) => match internal_state.spend_member_budget(signerkey, cost) {
+                Ok(_) => Ok(()),
+                Err(_) => Err(ApplyError::InvalidTransaction(format!(
+                    "Plugging costs {}, more than your remaining budget on {}", cost, internal_state.get_name()
+                )))
+            },
+            _ => Err(ApplyError::InvalidTransaction(format!(
+                "You are not allowed to operate modules of the organisation {} !", internal_state.get_name()
+            )))
+        }
+    }
+
+    fn check_exists(name: &str, state: &OSCashierState, not_found: &str) -> Result<(),ApplyError> {
+        match state.does_entry_exist(name) {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(ApplyError::InvalidTransaction(not_found.to_string())),
+            Err(context_error) => Err(ApplyError::InternalError(format!(
+                "ContextError: {}", context_error.to_string()
+            )))
+        }
+    }
+
+    fn load_state(name: &str, state: &OSCashierState) -> Result<_InternalOSCashierState,ApplyError> {
+        match state.get_state(name.to_string(), String::new()) {
+            Ok(internal_state) => Ok(internal_state),
+            Err(context_error) => Err(ApplyError::InternalError(format!(
+                "ContextError: {}", context_error.to_string()
+            )))
+        }
+    }
+
+    fn save_state(name: &str, internal_state: _InternalOSCashierState, state: &mut OSCashierState) -> Result<(),ApplyError> {
+        match state.set_state(name, internal_state) {
+            Ok(_) => Ok(()),
+            Err(context_error) => Err(ApplyError::InternalError(format!(
+                "ContextError: {}", context_error.to_string()
+            )))
+        }
+    }
+
+    /*
+     * Hands `name_state` over to the owner of `buyer`, moving `price` from `buyer` to `payout` in the same go
+     *
+     * Signed by the seller or the buyer, so the name is in use either way, the buyer's account only when it signed
+     */
+    fn sell_name(mut name_state: _InternalOSCashierState, buyer: &str, price: f32, payout: &str, signerkey: &str, state: &mut OSCashierState) -> Result<(),ApplyError> {
+        let name = name_state.get_name();
+
+        if buyer == name || payout == name || buyer == payout {
+            return Err(ApplyError::InvalidTransaction("The account sold, the buyer and the payout account must all be different".to_string()))
+        }
+
+        OSCashierHandler::check_exists(buyer, state, "Buying user doesn't exist")?;
+        OSCashierHandler::check_exists(payout, state, "Payout user doesn't exist")?;
+
+        let mut buyer_state = OSCashierHandler::load_state(buyer, state)?;
+        OSCashierHandler::reject_if_multisig(&buyer_state)?;
+
+        // Listings and offers stored before prices were checked could hold NaN, which passes every comparison
+        if !price.is_finite() || price < 0.0 {
+            return Err(ApplyError::InvalidTransaction(format!("Invalid price: {}", price)))
+        }
+        if buyer_state.get_points() < price as f64 {
+            return Err(ApplyError::InvalidTransaction(format!("{} can't pay {}", buyer, price)))
+        }
+        if buyer_state.dec_points(price).is_err() {
+            return Err(ApplyError::InvalidTransaction( format!("Couldn't Decrement points from {}", buyer) ))
+        }
+
+        let old_key = name_state.get_key();
+        let new_key = buyer_state.get_key();
+        if new_key == signerkey {
+            OSCashierHandler::touch(&mut buyer_state, state)?;
+        }
+        OSCashierHandler::save_state(buyer, buyer_state, state)?;
+
+        let mut payout_state = OSCashierHandler::load_state(payout, state)?;
+        if payout_state.add_points(price).is_err() {
+            return Err(ApplyError::InvalidTransaction( format!("Couldn't Increment points of {}", payout) ))
+        }
+        OSCashierHandler::save_state(payout, payout_state, state)?;
+
+        name_state.transfer_ownership(new_key.clone());
+        OSCashierHandler::touch(&mut name_state, state)?;
+
+        if let Err(context_error) = state.remove_key_account(&old_key, &name)
+                                         .and_then(|_| state.add_key_account(&new_key, &name)) {
+            return Err(ApplyError::InternalError(format!(
+                "ContextError: {}", context_error.to_string()
+            )))
+        }
+
+        #[cfg(debug_assertions)]
+        println!("User \"{}\" -> Sold to \"{}\" for {}", name, buyer, price);
+
+        OSCashierHandler::save_state(&name, name_state, state)
+    }
+
+    // `default` if the setting isn't set
+    fn get_parsed_setting<T: std::str::FromStr>(key: &str, default: T, state: &OSCashierState) -> Result<T,ApplyError> {
+        match state.get_setting(key) {
+            Ok(Some(value)) => match value.trim().parse() {
+                Ok(parsed) => Ok(parsed),
+                Err(_) => Err(ApplyError::InvalidTransaction(format!("Invalid {} setting: \"{}\"", key, value)))
+            },
+            Ok(None) => Ok(default),
+            Err(context_error) => Err(ApplyError::InternalError(format!(
+                "ContextError: {}", context_error.to_string()
+            )))
+        }
+    }
+
+    // Same as get_parsed_setting, for amounts of points, which must be finite and not negative
+    fn get_amount_setting(key: &str, default: f32, state: &OSCashierState) -> Result<f32,ApplyError> {
+        match state.get_setting(key) {
+            Ok(Some(value)) => match value.trim().parse::<f32>() {
+                Ok(amount) if amount.is_finite() && amount >= 0.0 => Ok(amount),
+                _ => Err(ApplyError::InvalidTransaction(format!("Invalid {} setting: \"{}\"", key, value)))
+            },
+            Ok(None) => Ok(default),
+            Err(context_error) => Err(ApplyError::InternalError(format!(
+                "ContextError: {}", context_error.to_string()
+            )))
+        }
+    }
+
+    // Memo and idempotency key checks shared by the transfer actions, records the key in `payer_state`
+    fn check_transfer_extras(payload: &OSCashierPayload, payer_state: &mut _InternalOSCashierState, state: &OSCashierState) -> Result<(),ApplyError> {
+        if payload.get_memo().len() > MAX_MEMO_LENGTH {
+            return Err(ApplyError::InvalidTransaction(format!("Memo is longer than {} bytes", MAX_MEMO_LENGTH)))
+        }
+
+        let idempotency_key = payload.get_idempotency_key();
+        if idempotency_key.is_empty() {
+            return Ok(())
+        }
+
+        let window = OSCashierHandler::get_parsed_setting(SETTING_IDEMPOTENCY_WINDOW, DEFAULT_IDEMPOTENCY_WINDOW, state)?;
+        let now = OSCashierHandler::get_chain_time(state)?;
+        match payer_state.record_idempotency_key(idempotency_key.clone(), window, now) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(ApplyError::InvalidTransaction(format!(
+                "Duplicate transfer, idempotency key \"{}\" was already used by {}", idempotency_key, payer_state.get_name()
+            )))
+        }
+    }
+
+    // Schedules go by the block timestamp, so every validator agrees on what is due
+    fn get_chain_time(state: &OSCashierState) -> Result<u64,ApplyError> {
+        match state.get_chain_timestamp() {
+            Ok(Some(timestamp)) => Ok(timestamp),
+            Ok(None) => Err(ApplyError::InvalidTransaction("No block info on chain, is the block_info transaction processor running ?".to_string())),
+            Err(context_error) => Err(ApplyError::InternalError(format!(
+                "ContextError: {}", context_error.to_string()
+            )))
+        }
+    }
+
+    // Same as get_chain_time, but None instead of rejecting when there's no block info
+    fn try_chain_time(state: &OSCashierState) -> Result<Option<u64>,ApplyError> {
+        match state.get_chain_timestamp() {
+            Ok(timestamp) => Ok(timestamp),
+            Err(context_error) => Err(ApplyError::InternalError(format!(
+                "ContextError: {}", context_error.to_string()
+            )))
+        }
+    }
+
+    /*
+     * Marks an account as in use, called for the accounts whose owner (or a signer/member it authorised) acted on them
+     *
+     * Dormancy goes by the block timestamp, without block info it can't be measured (nor reaped) so nothing changes
+     */
+    fn touch(internal_state: &mut _InternalOSCashierState, state: &OSCashierState) -> Result<(),ApplyError> {
+        if let Some(now) = OSCashierHandler::try_chain_time(state)? {
+            internal_state.touch(now);
+        }
+        Ok(())
+    }
+
+    fn is_admin(signerkey: &str, state: &OSCashierState) -> Result<bool,ApplyError> {
+        match state.get_setting(SETTING_ADMIN_KEYS) {
+            Ok(Some(admin_keys)) => Ok(admin_keys.split(',').any(|key| key.trim() == signerkey)),
+            Ok(None) => Ok(false),
+            Err(context_error) => Err(ApplyError::InternalError(format!(
+                "ContextError: {}", context_error.to_string()
+            )))
+        }
+    }
+
+    // Credits the treasury account, creating it if required
+    fn credit_treasury(points: f64, state: &mut OSCashierState) -> Result<(),ApplyError> {
+        let mut treasury_state = match state.get_treasury_state() {
+            Ok(treasury_state) => treasury_state,
+            Err(context_error) => return Err(ApplyError::InternalError(format!(
+                "ContextError: {}", context_error.to_string()
+            )))
+        };
+
+        if treasury_state.add_points(points as f32).is_err() {
+            return Err(ApplyError::InvalidTransaction("Couldn't credit the treasury".to_string()))
+        }
+
+        match state.set_state(TREASURY_NAME, treasury_state) {
+            Ok(_) => Ok(()),
+            Err(context_error) => Err(ApplyError::InternalError(format!(
+                "ContextError: {}", context_error.to_string()
+            )))
+        }
+    }
+
+    // An empty `keys` turns multisig off
+    fn check_signers(keys: &[String], threshold: u32) -> Result<(),ApplyError> {
+        if keys.is_empty() {
+            return Ok(())
+        }
+
+        for (i, key) in keys.iter().enumerate() {
+            if !OSCashierHandler::is_valid_public_key(key) {
+                return Err(ApplyError::InvalidTransaction(format!("Invalid public key: \"{}\"", key)))
+            }
+            if keys[..i].contains(key) {
+                return Err(ApplyError::InvalidTransaction(format!("Signer {} repeated", key)))
+            }
+        }
+
+        if threshold == 0 || threshold as usize > keys.len() {
+            return Err(ApplyError::InvalidTransaction(format!(
+                "Threshold must be between 1 and {}, got {}", keys.len(), threshold
+            )))
+        }
+        Ok(())
+    }
 }
 
 impl OSCashierHandler {
+    /*
+     * Who may register is set by `os_cashier.registration_mode`, any one of the listed modes is enough:
+     *  open    -> anyone (the default)
+     *  pow     -> payload has a solution to the registration puzzle, see pow.rs
+     *  sponsor -> an existing account (`sponsor`, signed by its key) vouches for it
+     *  fee     -> same as sponsor, but the sponsor also pays `os_cashier.registration_fee` to the treasury
+     *
+     * A sponsor may register the name for someone else's key, by passing it as `public_key`
+     */
     pub fn register(&self, signerkey: String, payload: &OSCashierPayload, state: &mut OSCashierState) -> Result<(),ApplyError> {
         let username = payload.get_name();
+        let sponsor = payload.get_sponsor();
+        let owner_key = if payload.get_public_key().is_empty() { signerkey.clone() } else { payload.get_public_key() };
+
+        if let Err(reason) = username::validate(&username) {
+            return Err(ApplyError::InvalidTransaction(reason))
+        }
+
+        if owner_key != signerkey {
+            if sponsor.is_empty() {
+                return Err(ApplyError::InvalidTransaction("Registering for another key needs a sponsor".to_string()))
+            }
+            if !OSCashierHandler::is_valid_public_key(&owner_key) {
+                return Err(ApplyError::InvalidTransaction(format!("Invalid public key: \"{}\"", owner_key)))
+            }
+        }
 
         match state.does_entry_exist(&username) {
             Ok(exists) => {
@@ -45,16 +346,88 @@ impl OSCashierHandler {
             }
         };
 
-        match state.get_state(username.clone(), signerkey) {
-            Ok(internal_state) => {
-                match state.set_state(&username, internal_state) {
-                    Ok(_) => Ok(()),
-                    Err(context_error) => Err(ApplyError::InternalError(format!(
+        let modes: String = OSCashierHandler::get_parsed_setting(SETTING_REGISTRATION_MODE, "open".to_string(), state)?;
+        let modes: Vec<&str> = modes.split(',').map(|mode| mode.trim()).collect();
+
+        let fee: f32 = if modes.contains(&"open") {
+            0.0
+        } else if modes.contains(&"pow") && pow::is_solved(
+            &username, &owner_key, payload.get_pow_nonce(),
+            OSCashierHandler::get_parsed_setting(SETTING_POW_DIFFICULTY, DEFAULT_POW_DIFFICULTY, state)?
+        ) {
+            0.0
+        } else if !sponsor.is_empty() && modes.contains(&"sponsor") {
+            0.0
+        } else if !sponsor.is_empty() && modes.contains(&"fee") {
+            OSCashierHandler::get_amount_setting(SETTING_REGISTRATION_FEE, 0.0, state)?
+        } else {
+            return Err(ApplyError::InvalidTransaction(format!("Registration requires one of: {}", modes.join(", "))))
+        };
+
+        if !sponsor.is_empty() {
+            match state.does_entry_exist(&sponsor) {
+                Ok(exists) => {
+                    if !exists {
+                        return Err(ApplyError::InvalidTransaction("Sponsoring user doesn't exist".to_string()))
+                    }
+                },
+                Err(e) => {
+                    return Err(ApplyError::InternalError(format!(
+                        "ContextError: {}", e.to_string()
+                    )));
+                }
+            };
+
+            let mut sponsor_state = match state.get_state(sponsor.clone(), signerkey.clone()) {
+                Ok(sponsor_state) => sponsor_state,
+                Err(context_error) => return Err(ApplyError::InternalError(format!(
+                    "ContextError: {}", context_error.to_string()
+                )))
+            };
+
+            OSCashierHandler::reject_if_multisig(&sponsor_state)?;
+
+            if sponsor_state.get_key() != signerkey {
+                return Err(ApplyError::InvalidTransaction(format!("You can't sponsor with {}'s account !", sponsor)))
+            }
+
+            if fee > 0.0 {
+                if sponsor_state.get_points() < fee as f64 {
+                    return Err(ApplyError::InvalidTransaction(format!("{} can't pay the registration fee of {}", sponsor, fee)))
+                }
+                if sponsor_state.dec_points(fee).is_err() {
+                    return Err(ApplyError::InvalidTransaction( format!("Couldn't Decrement points from {}", sponsor) ))
+                }
+                OSCashierHandler::touch(&mut sponsor_state, state)?;
+                if let Err(context_error) = state.set_state(&sponsor, sponsor_state) {
+                    return Err(ApplyError::InternalError(format!(
+                        "ContextError: {}", context_error.to_string()
+                    )))
+                }
+                OSCashierHandler::credit_treasury(fee as f64, state)?;
+            }
+        }
+
+        let faucet_amount = OSCashierHandler::get_amount_setting(SETTING_FAUCET_AMOUNT, DEFAULT_INIT_POINTS, state)?;
+
+        match state.get_state(username.clone(), owner_key.clone()) {
+            Ok(mut internal_state) => {
+                internal_state.set_points(faucet_amount as f64);
+
+                if let Err(context_error) = state.set_state(&username, internal_state) {
+                    return Err(ApplyError::InternalError(format!(
                         "ContextError: {}", context_error.to_string()
                     )))
                 }
             },
-            Err(e) => Err(ApplyError::InternalError(format!("ContextError: {}", e.to_string())))
+            Err(e) => return Err(ApplyError::InternalError(format!("ContextError: {}", e.to_string())))
+        };
+
+        match state.add_key_account(&owner_key, &username) {
+            Ok(_) => Ok(()),
+            Err(context_error) => Err(ApplyError::InternalError(format!(
+                "ContextError: {}", context_error.to_string()
+            )))
         }
     }
 
@@ -74,9 +447,19 @@ impl OSCashierHandler {
             }
         };
 
-        match state.get_state(username.clone(), signerkey) {
+        match state.get_state(username.clone(), signerkey.clone()) {
             Ok(mut internal_state) => {
-                match internal_state.add_mod(payload.get_module_name()) {
+                OSCashierHandler::reject_if_multisig(&internal_state)?;
+                if !internal_state.is_org() && internal_state.get_key() != signerkey {
+                    return Err(ApplyError::InvalidTransaction("You are not allowed to plug modules into someone else's account !".to_string()));
+                }
+
+                let plug_cost = internal_state.get_module_rating(&payload.get_module_name()).map_or(0.0, |rating| rating.abs());
+                OSCashierHandler::check_org_operator(&mut internal_state, &signerkey, plug_cost)?;
+                OSCashierHandler::touch(&mut internal_state, state)?;
+
+                let plugged_at = OSCashierHandler::try_chain_time(state)?;
+                match internal_state.add_mod(payload.get_module_name(), plugged_at) {
                     Ok(_) => {
                         #[cfg(debug_assertions)]
                         println!("User \"{}\" -> After Plug: {:#?}", username, internal_state);
@@ -116,8 +499,15 @@ impl OSCashierHandler {
             }
         };
 
-        match state.get_state(username.clone(), signerkey) {
+        match state.get_state(username.clone(), signerkey.clone()) {
             Ok(mut internal_state) => {
+                OSCashierHandler::reject_if_multisig(&internal_state)?;
+                if !internal_state.is_org() && internal_state.get_key() != signerkey {
+                    return Err(ApplyError::InvalidTransaction("You are not allowed to unplug modules of someone else's account !".to_string()));
+                }
+                OSCashierHandler::check_org_operator(&mut internal_state, &signerkey, 0.0)?;
+                OSCashierHandler::touch(&mut internal_state, state)?;
+
                 match internal_state.remove_mod(&payload.get_module_name()) {
                     Ok(_) => {},
                     Err(_) => {
@@ -141,15 +531,13 @@ impl OSCashierHandler {
         }
     }
 
-    pub fn transfer(&self, signerkey: String, payload: &OSCashierPayload, state: &mut OSCashierState) -> Result<(),ApplyError> {
+    pub fn claim(&self, signerkey: String, payload: &OSCashierPayload, state: &mut OSCashierState) -> Result<(),ApplyError> {
         let username = payload.get_name();
-        let receiver = payload.get_receiver();
 
-        // Check whether payer exists
         match state.does_entry_exist(&username) {
             Ok(exists) => {
                 if !exists {
-                    return Err(ApplyError::InvalidTransaction("Sending user doesn't exist".to_string()))
+                    return Err(ApplyError::InvalidTransaction("User doesn't exist".to_string()))
                 }
             },
             Err(e) => {
@@ -158,11 +546,46 @@ impl OSCashierHandler {
                 )));
             }
         };
-        // Check whether receiver exists
-        match state.does_entry_exist(&receiver) {
+
+        match state.get_state(username.clone(), signerkey.clone()) {
+            Ok(mut internal_state) => {
+                OSCashierHandler::reject_if_multisig(&internal_state)?;
+                if !internal_state.is_org() && internal_state.get_key() != signerkey {
+                    return Err(ApplyError::InvalidTransaction("You are not allowed to claim someone else's rewards !".to_string()));
+                }
+                OSCashierHandler::check_org_operator(&mut internal_state, &signerkey, 0.0)?;
+                OSCashierHandler::touch(&mut internal_state, state)?;
+
+                match internal_state.claim_mod(&payload.get_module_name()) {
+                    Ok(_claimed) => {
+                        #[cfg(debug_assertions)]
+                        println!("User \"{}\" -> Claimed {} from {}: {:#?}", username, _claimed, payload.get_module_name(), internal_state);
+                    },
+                    Err(_) => {
+                        return Err(ApplyError::InvalidTransaction( format!("Module {} not plugged for user {}", payload.get_module_name(), username) ))
+                    }
+                }
+
+                match state.set_state(&username, internal_state) {
+                    Ok(_) => Ok(()),
+                    Err(context_error) => Err(ApplyError::InternalError(format!(
+                        "ContextError: {}", context_error.to_string()
+                    )))
+                }
+            },
+            Err(context_error) => Err(ApplyError::InternalError(format!(
+                "ContextError: {}", context_error.to_string()
+            )))
+        }
+    }
+
+    pub fn reboot(&self, signerkey: String, payload: &OSCashierPayload, state: &mut OSCashierState) -> Result<(),ApplyError> {
+        let username = payload.get_name();
+
+        match state.does_entry_exist(&username) {
             Ok(exists) => {
                 if !exists {
-                    return Err(ApplyError::InvalidTransaction("Receiving user doesn't exist".to_string()))
+                    return Err(ApplyError::InvalidTransaction("User doesn't exist".to_string()))
                 }
             },
             Err(e) => {
@@ -172,24 +595,1263 @@ impl OSCashierHandler {
             }
         };
 
-        let mut payer_state = match state.get_state(username.clone(), signerkey.clone()) {
-            Ok(state) => state,
+        let mut internal_state = match state.get_state(username.clone(), signerkey.clone()) {
+            Ok(internal_state) => internal_state,
             Err(context_error) => return Err(ApplyError::InternalError(format!(
                 "ContextError: {}", context_error.to_string()
             )))
         };
-        let mut receiver_state = match state.get_state(receiver.clone(), signerkey.clone()) {     // TODO: It's public key won't be same and we don't care either
-            Ok(state) => state,
+
+        OSCashierHandler::reject_if_multisig(&internal_state)?;
+        if !internal_state.is_org() && internal_state.get_key() != signerkey {
+            return Err(ApplyError::InvalidTransaction("You are not allowed to reboot someone else's account !".to_string()));
+        }
+
+        // Re-plugging the new set costs the same as plugging each module, members pay it from their budget
+        let plug_cost: f32 = payload.get_modules().iter()
+            .map(|module_name| internal_state.get_module_rating(module_name).map_or(0.0, |rating| rating.abs()))
+            .sum();
+        OSCashierHandler::check_org_operator(&mut internal_state, &signerkey, plug_cost)?;
+        OSCashierHandler::touch(&mut internal_state, state)?;
+
+        // {module -> settled points}, goes in the receipt so the user can see what each module earned
+        let breakdown = match internal_state.remove_all_mods() {
+            Ok(breakdown) => breakdown,
+            Err(_) => {
+                return Err(ApplyError::InvalidTransaction( format!("Couldn't unplug modules of user {}", username) ))
+            }
+        };
+
+        // Nothing is written till the end, so a bad module here rejects the whole reboot
+        let plugged_at = OSCashierHandler::try_chain_time(state)?;
+        for module_name in payload.get_modules() {
+            if internal_state.add_mod(module_name.clone(), plugged_at).is_err() {
+                return Err(ApplyError::InvalidTransaction( format!("Couldn't plug module {} after reboot", module_name) ))
+            }
+        }
+
+        #[cfg(debug_assertions)]
+        println!("User \"{}\" -> After reboot: {:#?}\nSettled: {:#?}", username, internal_state, breakdown);
+
+        let receipt_bytes = match serde_cbor::to_vec(&breakdown) {
+            Ok(bytes) => bytes,
+            Err(e) => return Err(ApplyError::InternalError(format!(
+                "Couldn't serialize reboot receipt: {}", e.to_string()
+            )))
+        };
+
+        if let Err(context_error) = state.add_receipt_data(&receipt_bytes) {
+            return Err(ApplyError::InternalError(format!(
+                "ContextError: {}", context_error.to_string()
+            )))
+        }
+
+        match state.set_state(&username, internal_state) {
+            Ok(_) => Ok(()),
+            Err(context_error) => Err(ApplyError::InternalError(format!(
+                "ContextError: {}", context_error.to_string()
+            )))
+        }
+    }
+
+    pub fn rotate_key(&self, signerkey: String, payload: &OSCashierPayload, state: &mut OSCashierState) -> Result<(),ApplyError> {
+        let username = payload.get_name();
+        let new_key = payload.get_public_key();
+
+        if !OSCashierHandler::is_valid_public_key(&new_key) {
+            return Err(ApplyError::InvalidTransaction(format!("Invalid public key: \"{}\"", new_key)))
+        }
+
+        match state.does_entry_exist(&username) {
+            Ok(exists) => {
+                if !exists {
+                    return Err(ApplyError::InvalidTransaction("User doesn't exist".to_string()))
+                }
+            },
+            Err(e) => {
+                return Err(ApplyError::InternalError(format!(
+                    "ContextError: {}", e.to_string()
+                )));
+            }
+        };
+
+        let mut internal_state = match state.get_state(username.clone(), signerkey.clone()) {
+            Ok(internal_state) => internal_state,
             Err(context_error) => return Err(ApplyError::InternalError(format!(
                 "ContextError: {}", context_error.to_string()
             )))
         };
 
-        let payer_public_key = payer_state.get_key();
+        OSCashierHandler::reject_if_multisig(&internal_state)?;
 
-        if payer_public_key != signerkey {
-            return Err(ApplyError::InvalidTransaction("You are not allowed to transfer someone else's points !".to_string()));
-        };
+        let recovery_key = internal_state.get_recovery_key();
+        let old_key = internal_state.get_key();
+
+        if signerkey == old_key {
+            internal_state.set_key(new_key);
+        } else if !recovery_key.is_empty() && signerkey == recovery_key {
+            // The timelock goes by the block timestamp, so every validator agrees on it
+            let now = OSCashierHandler::get_chain_time(state)?;
+
+            match internal_state.recover_key(new_key, now) {
+                Ok(_rotated) => {
+                    #[cfg(debug_assertions)]
+                    println!("User \"{}\" -> Recovery {}", username, if _rotated { "rotated the key" } else { "timelock started" });
+                },
+                Err(remaining) => {
+                    return Err(ApplyError::InvalidTransaction(format!(
+                        "Recovery of {} is timelocked for {} more seconds", username, remaining
+                    )))
+                }
+            }
+        } else {
+            return Err(ApplyError::InvalidTransaction("Only the account key or its recovery key can rotate the key !".to_string()))
+        }
+
+        OSCashierHandler::touch(&mut internal_state, state)?;
+
+        // Recovery may have only started the timelock, the index moves only when the key actually changed
+        let new_key = internal_state.get_key();
+        if new_key != old_key {
+            if let Err(context_error) = state.remove_key_account(&old_key, &username)
+                                             .and_then(|_| state.add_key_account(&new_key, &username)) {
+                return Err(ApplyError::InternalError(format!(
+                    "ContextError: {}", context_error.to_string()
+                )))
+            }
+        }
+
+        match state.set_state(&username, internal_state) {
+            Ok(_) => Ok(()),
+            Err(context_error) => Err(ApplyError::InternalError(format!(
+                "ContextError: {}", context_error.to_string()
+            )))
+        }
+    }
+
+    pub fn set_recovery(&self, signerkey: String, payload: &OSCashierPayload, state: &mut OSCashierState) -> Result<(),ApplyError> {
+        let username = payload.get_name();
+        let recovery_key = payload.get_public_key();    // empty removes the recovery key
+
+        if !recovery_key.is_empty() && !OSCashierHandler::is_valid_public_key(&recovery_key) {
+            return Err(ApplyError::InvalidTransaction(format!("Invalid public key: \"{}\"", recovery_key)))
+        }
+
+        match state.does_entry_exist(&username) {
+            Ok(exists) => {
+                if !exists {
+                    return Err(ApplyError::InvalidTransaction("User doesn't exist".to_string()))
+                }
+            },
+            Err(e) => {
+                return Err(ApplyError::InternalError(format!(
+                    "ContextError: {}", e.to_string()
+                )));
+            }
+        };
+
+        let mut internal_state = match state.get_state(username.clone(), signerkey.clone()) {
+            Ok(internal_state) => internal_state,
+            Err(context_error) => return Err(ApplyError::InternalError(format!(
+                "ContextError: {}", context_error.to_string()
+            )))
+        };
+
+        OSCashierHandler::reject_if_multisig(&internal_state)?;
+        if internal_state.get_key() != signerkey {
+            return Err(ApplyError::InvalidTransaction("Only the account key can set a recovery key !".to_string()));
+        }
+
+        internal_state.set_recovery(recovery_key, payload.get_timelock());
+        OSCashierHandler::touch(&mut internal_state, state)?;
+
+        match state.set_state(&username, internal_state) {
+            Ok(_) => Ok(()),
+            Err(context_error) => Err(ApplyError::InternalError(format!(
+                "ContextError: {}", context_error.to_string()
+            )))
+        }
+    }
+
+    pub fn set_signers(&self, signerkey: String, payload: &OSCashierPayload, state: &mut OSCashierState) -> Result<(),ApplyError> {
+        let username = payload.get_name();
+
+        OSCashierHandler::check_signers(&payload.get_keys(), payload.get_threshold())?;
+
+        match state.does_entry_exist(&username) {
+            Ok(exists) => {
+                if !exists {
+                    return Err(ApplyError::InvalidTransaction("User doesn't exist".to_string()))
+                }
+            },
+            Err(e) => {
+                return Err(ApplyError::InternalError(format!(
+                    "ContextError: {}", e.to_string()
+                )));
+            }
+        };
+
+        let mut internal_state = match state.get_state(username.clone(), signerkey.clone()) {
+            Ok(internal_state) => internal_state,
+            Err(context_error) => return Err(ApplyError::InternalError(format!(
+                "ContextError: {}", context_error.to_string()
+            )))
+        };
+
+        // Once multisig, changing the signers needs a proposal too
+        OSCashierHandler::reject_if_multisig(&internal_state)?;
+
+        if internal_state.get_key() != signerkey {
+            return Err(ApplyError::InvalidTransaction("Only the account key can set signers !".to_string()));
+        }
+
+        internal_state.set_signers(payload.get_keys(), payload.get_threshold());
+        OSCashierHandler::touch(&mut internal_state, state)?;
+
+        match state.set_state(&username, internal_state) {
+            Ok(_) => Ok(()),
+            Err(context_error) => Err(ApplyError::InternalError(format!(
+                "ContextError: {}", context_error.to_string()
+            )))
+        }
+    }
+
+    pub fn propose(&self, signerkey: String, payload: &OSCashierPayload, state: &mut OSCashierState) -> Result<(),ApplyError> {
+        let username = payload.get_name();
+
+        let operation = match payload.get_operation() {
+            Some(operation) => operation.clone(),
+            None => return Err(ApplyError::InvalidTransaction("Propose requires an operation".to_string()))
+        };
+
+        if operation.get_name() != username {
+            return Err(ApplyError::InvalidTransaction("Proposed operation must be on the proposing account".to_string()))
+        }
+
+        match operation.get_action() {
+            Some(Actions::PlugMod) | Some(Actions::UnplugMod) | Some(Actions::Claim) | Some(Actions::Transfer) => {},
+            Some(Actions::SetSigners) => OSCashierHandler::check_signers(&operation.get_keys(), operation.get_threshold())?,
+            _ => return Err(ApplyError::InvalidTransaction(format!("Action {} can't be proposed", operation.get_action_name())))
+        };
+
+        match state.does_entry_exist(&username) {
+            Ok(exists) => {
+                if !exists {
+                    return Err(ApplyError::InvalidTransaction("User doesn't exist".to_string()))
+                }
+            },
+            Err(e) => {
+                return Err(ApplyError::InternalError(format!(
+                    "ContextError: {}", e.to_string()
+                )));
+            }
+        };
+
+        let mut internal_state = match state.get_state(username.clone(), signerkey.clone()) {
+            Ok(internal_state) => internal_state,
+            Err(context_error) => return Err(ApplyError::InternalError(format!(
+                "ContextError: {}", context_error.to_string()
+            )))
+        };
+
+        if !internal_state.is_multisig() {
+            return Err(ApplyError::InvalidTransaction(format!("{} is not a multisig account", username)))
+        }
+        if !internal_state.is_signer(&signerkey) {
+            return Err(ApplyError::InvalidTransaction(format!("You are not a signer of {} !", username)))
+        }
+
+        let created_at = OSCashierHandler::try_chain_time(state)?.unwrap_or(0);
+        let proposal_id = internal_state.add_proposal(operation, signerkey, created_at);
+        OSCashierHandler::touch(&mut internal_state, state)?;
+
+        #[cfg(debug_assertions)]
+        println!("User \"{}\" -> Proposal {} added", username, proposal_id);
+
+        self.execute_if_approved(&mut internal_state, proposal_id, state)?;
+
+        match state.set_state(&username, internal_state) {
+            Ok(_) => Ok(()),
+            Err(context_error) => Err(ApplyError::InternalError(format!(
+                "ContextError: {}", context_error.to_string()
+            )))
+        }
+    }
+
+    pub fn approve_proposal(&self, signerkey: String, payload: &OSCashierPayload, state: &mut OSCashierState) -> Result<(),ApplyError> {
+        let username = payload.get_name();
+        let proposal_id = payload.get_proposal_id();
+
+        match state.does_entry_exist(&username) {
+            Ok(exists) => {
+                if !exists {
+                    return Err(ApplyError::InvalidTransaction("User doesn't exist".to_string()))
+                }
+            },
+            Err(e) => {
+                return Err(ApplyError::InternalError(format!(
+                    "ContextError: {}", e.to_string()
+                )));
+            }
+        };
+
+        let mut internal_state = match state.get_state(username.clone(), signerkey.clone()) {
+            Ok(internal_state) => internal_state,
+            Err(context_error) => return Err(ApplyError::InternalError(format!(
+                "ContextError: {}", context_error.to_string()
+            )))
+        };
+
+        if !internal_state.is_signer(&signerkey) {
+            return Err(ApplyError::InvalidTransaction(format!("You are not a signer of {} !", username)))
+        }
+
+        match internal_state.get_proposal_mut(proposal_id) {
+            Some(proposal) => {
+                if !proposal.approve(signerkey) {
+                    return Err(ApplyError::InvalidTransaction(format!("Proposal {} already approved by you", proposal_id)))
+                }
+            },
+            None => {
+                return Err(ApplyError::InvalidTransaction(format!("No pending proposal {} for {}", proposal_id, username)))
+            }
+        };
+
+        OSCashierHandler::touch(&mut internal_state, state)?;
+        self.execute_if_approved(&mut internal_state, proposal_id, state)?;
+
+        match state.set_state(&username, internal_state) {
+            Ok(_) => Ok(()),
+            Err(context_error) => Err(ApplyError::InternalError(format!(
+                "ContextError: {}", context_error.to_string()
+            )))
+        }
+    }
+
+    /*
+     * Runs the proposal once it has enough approvals, on the already loaded account state, the caller saves it
+     *
+     * If the operation itself fails (eg. module already plugged), the whole approving transaction is invalid,
+     * so the proposal stays pending and can be approved again later
+     */
+    fn execute_if_approved(&self, internal_state: &mut _InternalOSCashierState, proposal_id: u64, state: &mut OSCashierState) -> Result<(),ApplyError> {
+        if !internal_state.is_proposal_approved(proposal_id) {
+            return Ok(())
+        }
+
+        let proposal = match internal_state.remove_proposal(proposal_id) {
+            Some(proposal) => proposal,
+            None => return Ok(())
+        };
+        let operation = proposal.get_operation();
+        let username = internal_state.get_name();
+
+        #[cfg(debug_assertions)]
+        println!("User \"{}\" -> Executing proposal {}: {:?}", username, proposal_id, operation);
+
+        match operation.get_action() {
+            Some(Actions::PlugMod) => match internal_state.add_mod(operation.get_module_name(), OSCashierHandler::try_chain_time(state)?) {
+                Ok(_) => Ok(()),
+                Err(_) => Err(ApplyError::InvalidTransaction(format!("Couldn't plug module {}", operation.get_module_name())))
+            },
+            Some(Actions::UnplugMod) => match internal_state.remove_mod(&operation.get_module_name()) {
+                Ok(_) => Ok(()),
+                Err(_) => Err(ApplyError::InvalidTransaction(format!("Module {} not available for user {}", operation.get_module_name(), username)))
+            },
+            Some(Actions::Claim) => match internal_state.claim_mod(&operation.get_module_name()) {
+                Ok(_) => Ok(()),
+                Err(_) => Err(ApplyError::InvalidTransaction(format!("Module {} not plugged for user {}", operation.get_module_name(), username)))
+            },
+            Some(Actions::SetSigners) => {
+                internal_state.set_signers(operation.get_keys(), operation.get_threshold());
+                Ok(())
+            },
+            Some(Actions::Transfer) => {
+                let receiver = operation.get_receiver();
+
+                // the account state is saved by the caller after this, that would overwrite the credit
+                if receiver == username {
+                    return Err(ApplyError::InvalidTransaction("Can't transfer to the same account".to_string()))
+                }
+
+                match state.does_entry_exist(&receiver) {
+                    Ok(exists) => {
+                        if !exists {
+                            return Err(ApplyError::InvalidTransaction("Receiving user doesn't exist".to_string()))
+                        }
+                    },
+                    Err(e) => {
+                        return Err(ApplyError::InternalError(format!(
+                            "ContextError: {}", e.to_string()
+                        )));
+                    }
+                };
+
+                let mut receiver_state = match state.get_state(receiver.clone(), String::new()) {
+                    Ok(state) => state,
+                    Err(context_error) => return Err(ApplyError::InternalError(format!(
+                        "ContextError: {}", context_error.to_string()
+                    )))
+                };
+
+                let transaction_amount = operation.get_amount().abs();
+                if internal_state.dec_points(transaction_amount).is_err() {
+                    return Err(ApplyError::InvalidTransaction( format!("Couldn't Decrement points from {}", username) ))
+                }
+                if receiver_state.add_points(transaction_amount).is_err() {
+                    return Err(ApplyError::InvalidTransaction( format!("Couldn't Increment points from {}", username) ))
+                }
+
+                match state.set_state(&receiver, receiver_state) {
+                    Ok(_) => Ok(()),
+                    Err(context_error) => Err(ApplyError::InternalError(format!(
+                        "ContextError: {}", context_error.to_string()
+                    )))
+                }
+            },
+            _ => Err(ApplyError::InvalidTransaction(format!("Action {} can't be proposed", operation.get_action_name())))
+        }
+    }
+
+    pub fn deregister(&self, signerkey: String, payload: &OSCashierPayload, state: &mut OSCashierState) -> Result<(),ApplyError> {
+        let username = payload.get_name();
+        let sweep_to = payload.get_receiver();  // empty -> treasury
+
+        match state.does_entry_exist(&username) {
+            Ok(exists) => {
+                if !exists {
+                    return Err(ApplyError::InvalidTransaction("User doesn't exist".to_string()))
+                }
+            },
+            Err(e) => {
+                return Err(ApplyError::InternalError(format!(
+                    "ContextError: {}", e.to_string()
+                )));
+            }
+        };
+
+        let mut internal_state = match state.get_state(username.clone(), signerkey.clone()) {
+            Ok(internal_state) => internal_state,
+            Err(context_error) => return Err(ApplyError::InternalError(format!(
+                "ContextError: {}", context_error.to_string()
+            )))
+        };
+
+        OSCashierHandler::reject_if_multisig(&internal_state)?;
+
+        if internal_state.get_key() != signerkey {
+            return Err(ApplyError::InvalidTransaction("You are not allowed to close someone else's account !".to_string()));
+        }
+        if internal_state.has_mods() {
+            return Err(ApplyError::InvalidTransaction(format!("Unplug all modules of {} before deregistering", username)))
+        }
+        if internal_state.has_escrows() {
+            return Err(ApplyError::InvalidTransaction(format!("{} has open escrows, release or refund them before deregistering", username)))
+        }
+        if internal_state.get_points() < 0.0 {
+            return Err(ApplyError::InvalidTransaction(format!("{} has a negative balance, settle it before deregistering", username)))
+        }
+        if sweep_to == username {
+            return Err(ApplyError::InvalidTransaction("Can't sweep the balance to the account being closed".to_string()))
+        }
+
+        let balance = internal_state.take_points();
+
+        if sweep_to.is_empty() {
+            OSCashierHandler::credit_treasury(balance, state)?;
+        } else {
+            match state.does_entry_exist(&sweep_to) {
+                Ok(exists) => {
+                    if !exists {
+                        return Err(ApplyError::InvalidTransaction("Receiving user doesn't exist".to_string()))
+                    }
+                },
+                Err(e) => {
+                    return Err(ApplyError::InternalError(format!(
+                        "ContextError: {}", e.to_string()
+                    )));
+                }
+            };
+
+            let mut receiver_state = match state.get_state(sweep_to.clone(), String::new()) {
+                Ok(receiver_state) => receiver_state,
+                Err(context_error) => return Err(ApplyError::InternalError(format!(
+                    "ContextError: {}", context_error.to_string()
+                )))
+            };
+
+            if receiver_state.add_points(balance as f32).is_err() {
+                return Err(ApplyError::InvalidTransaction( format!("Couldn't Increment points of {}", sweep_to) ))
+            }
+
+            if let Err(context_error) = state.set_state(&sweep_to, receiver_state) {
+                return Err(ApplyError::InternalError(format!(
+                    "ContextError: {}", context_error.to_string()
+                )))
+            }
+        }
+
+        #[cfg(debug_assertions)]
+        println!("User \"{}\" -> Deregistered, {} swept to \"{}\"", username, balance, if sweep_to.is_empty() { TREASURY_NAME } else { sweep_to.as_str() });
+
+        match state.remove_key_account(&signerkey, &username)
+                   .and_then(|_| state.delete_state(&username)) {
+            Ok(_) => Ok(()),
+            Err(context_error) => Err(ApplyError::InternalError(format!(
+                "ContextError: {}", context_error.to_string()
+            )))
+        }
+    }
+
+    // Admin cleanup of accounts nobody has used for `os_cashier.dormant_period` seconds
+    pub fn reap(&self, signerkey: String, payload: &OSCashierPayload, state: &mut OSCashierState) -> Result<(),ApplyError> {
+        let username = payload.get_name();
+
+        if !OSCashierHandler::is_admin(&signerkey, state)? {
+            return Err(ApplyError::InvalidTransaction("Only admins can reap accounts !".to_string()))
+        }
+
+        let dormant_period: u64 = match state.get_setting(SETTING_DORMANT_PERIOD) {
+            Ok(Some(period)) => match period.trim().parse() {
+                Ok(period) => period,
+                Err(_) => return Err(ApplyError::InvalidTransaction(format!("Invalid {} setting: \"{}\"", SETTING_DORMANT_PERIOD, period)))
+            },
+            Ok(None) => return Err(ApplyError::InvalidTransaction(format!("Reaping is disabled, {} is not set", SETTING_DORMANT_PERIOD))),
+            Err(context_error) => return Err(ApplyError::InternalError(format!(
+                "ContextError: {}", context_error.to_string()
+            )))
+        };
+
+        if username == TREASURY_NAME {
+            return Err(ApplyError::InvalidTransaction("The treasury can't be reaped".to_string()))
+        }
+
+        match state.does_entry_exist(&username) {
+            Ok(exists) => {
+                if !exists {
+                    return Err(ApplyError::InvalidTransaction("User doesn't exist".to_string()))
+                }
+            },
+            Err(e) => {
+                return Err(ApplyError::InternalError(format!(
+                    "ContextError: {}", e.to_string()
+                )));
+            }
+        };
+
+        let mut internal_state = match state.get_state(username.clone(), signerkey) {
+            Ok(internal_state) => internal_state,
+            Err(context_error) => return Err(ApplyError::InternalError(format!(
+                "ContextError: {}", context_error.to_string()
+            )))
+        };
+
+        // Plugged modules are still accruing, that's use of the account
+        let now = OSCashierHandler::get_chain_time(state)?;
+        if internal_state.has_mods() || !internal_state.is_dormant(dormant_period, now) {
+            return Err(ApplyError::InvalidTransaction(format!("{} is not dormant", username)))
+        }
+        // Open escrows become refundable after their deadline, reap after that
+        if internal_state.has_escrows() {
+            return Err(ApplyError::InvalidTransaction(format!("{} has open escrows", username)))
+        }
+
+        // A negative balance is written off, it's not the treasury's debt
+        let balance = internal_state.take_points();
+        if balance > 0.0 {
+            OSCashierHandler::credit_treasury(balance, state)?;
+        }
+
+        match state.remove_key_account(&internal_state.get_key(), &username)
+                   .and_then(|_| state.delete_state(&username)) {
+            Ok(_) => Ok(()),
+            Err(context_error) => Err(ApplyError::InternalError(format!(
+                "ContextError: {}", context_error.to_string()
+            )))
+        }
+    }
+
+    // Gives the account away, same as a sale for 0 but to any key
+    pub fn transfer_name(&self, signerkey: String, payload: &OSCashierPayload, state: &mut OSCashierState) -> Result<(),ApplyError> {
+        let username = payload.get_name();
+        let new_key = payload.get_public_key();
+
+        if !OSCashierHandler::is_valid_public_key(&new_key) {
+            return Err(ApplyError::InvalidTransaction(format!("Invalid public key: \"{}\"", new_key)))
+        }
+
+        OSCashierHandler::check_exists(&username, state, "User doesn't exist")?;
+        let mut internal_state = OSCashierHandler::load_state(&username, state)?;
+
+        OSCashierHandler::reject_if_multisig(&internal_state)?;
+
+        if internal_state.get_key() != signerkey {
+            return Err(ApplyError::InvalidTransaction("You are not allowed to give away someone else's account !".to_string()));
+        }
+
+        internal_state.transfer_ownership(new_key.clone());
+        OSCashierHandler::touch(&mut internal_state, state)?;
+
+        if let Err(context_error) = state.remove_key_account(&signerkey, &username)
+                                         .and_then(|_| state.add_key_account(&new_key, &username)) {
+            return Err(ApplyError::InternalError(format!(
+                "ContextError: {}", context_error.to_string()
+            )))
+        }
+
+        OSCashierHandler::save_state(&username, internal_state, state)
+    }
+
+    // A price of 0 takes the listing down
+    pub fn list_name(&self, signerkey: String, payload: &OSCashierPayload, state: &mut OSCashierState) -> Result<(),ApplyError> {
+        let username = payload.get_name();
+        let price = payload.get_amount();
+        let payout = payload.get_receiver();
+
+        OSCashierHandler::check_exists(&username, state, "User doesn't exist")?;
+        let mut internal_state = OSCashierHandler::load_state(&username, state)?;
+
+        OSCashierHandler::reject_if_multisig(&internal_state)?;
+
+        if internal_state.get_key() != signerkey {
+            return Err(ApplyError::InvalidTransaction("You are not allowed to sell someone else's account !".to_string()));
+        }
+
+        if !price.is_finite() {
+            return Err(ApplyError::InvalidTransaction(format!("Invalid price: {}", price)))
+        } else if price < 0.0 {
+            return Err(ApplyError::InvalidTransaction("Price can't be negative".to_string()))
+        } else if price == 0.0 {
+            internal_state.set_listing(None);
+        } else {
+            if payout == username {
+                return Err(ApplyError::InvalidTransaction("Payout account can't be the account being sold".to_string()))
+            }
+            OSCashierHandler::check_exists(&payout, state, "Payout user doesn't exist")?;
+
+            internal_state.set_listing(Some(Listing::new(price, payout)));
+        }
+        OSCashierHandler::touch(&mut internal_state, state)?;
+
+        OSCashierHandler::save_state(&username, internal_state, state)
+    }
+
+    // `amount` is the most the buyer agrees to pay, so a raised price can't be slipped in before this executes
+    pub fn buy_name(&self, signerkey: String, payload: &OSCashierPayload, state: &mut OSCashierState) -> Result<(),ApplyError> {
+        let username = payload.get_name();
+        let buyer = payload.get_sponsor();
+
+        OSCashierHandler::check_exists(&username, state, "User doesn't exist")?;
+        let name_state = OSCashierHandler::load_state(&username, state)?;
+
+        let (price, payout) = match name_state.get_listing() {
+            Some(listing) => (listing.get_price(), listing.get_payout()),
+            None => return Err(ApplyError::InvalidTransaction(format!("{} is not for sale", username)))
+        };
+
+        // NaN compares false to everything, it would skip the check below
+        let max_price = payload.get_amount();
+        if !max_price.is_finite() {
+            return Err(ApplyError::InvalidTransaction(format!("Invalid max price: {}", max_price)))
+        }
+        if price > max_price {
+            return Err(ApplyError::InvalidTransaction(format!("{} costs {}, more than {}", username, price, max_price)))
+        }
+
+        OSCashierHandler::check_exists(&buyer, state, "Buying user doesn't exist")?;
+        if OSCashierHandler::load_state(&buyer, state)?.get_key() != signerkey {
+            return Err(ApplyError::InvalidTransaction(format!("You can't pay with {}'s account !", buyer)))
+        }
+
+        OSCashierHandler::sell_name(name_state, &buyer, price, &payout, &signerkey, state)
+    }
+
+    // Offers aren't escrowed, the buyer needs the balance when the offer gets accepted
+    pub fn offer_name(&self, signerkey: String, payload: &OSCashierPayload, state: &mut OSCashierState) -> Result<(),ApplyError> {
+        let username = payload.get_name();
+        let buyer = payload.get_sponsor();
+        let price = payload.get_amount();
+
+        if !price.is_finite() {
+            return Err(ApplyError::InvalidTransaction(format!("Invalid price: {}", price)))
+        }
+        if price < 0.0 {
+            return Err(ApplyError::InvalidTransaction("Price can't be negative".to_string()))
+        }
+        if buyer == username {
+            return Err(ApplyError::InvalidTransaction("Can't make an offer for your own account".to_string()))
+        }
+
+        OSCashierHandler::check_exists(&buyer, state, "Buying user doesn't exist")?;
+        if OSCashierHandler::load_state(&buyer, state)?.get_key() != signerkey {
+            return Err(ApplyError::InvalidTransaction(format!("You can't make offers from {}'s account !", buyer)))
+        }
+
+        OSCashierHandler::check_exists(&username, state, "User doesn't exist")?;
+        let mut internal_state = OSCashierHandler::load_state(&username, state)?;
+
+        internal_state.set_offer(buyer, price);
+
+        OSCashierHandler::save_state(&username, internal_state, state)
+    }
+
+    pub fn accept_offer(&self, signerkey: String, payload: &OSCashierPayload, state: &mut OSCashierState) -> Result<(),ApplyError> {
+        let username = payload.get_name();
+        let buyer = payload.get_sponsor();
+        let payout = payload.get_receiver();
+
+        OSCashierHandler::check_exists(&username, state, "User doesn't exist")?;
+        let name_state = OSCashierHandler::load_state(&username, state)?;
+
+        OSCashierHandler::reject_if_multisig(&name_state)?;
+
+        if name_state.get_key() != signerkey {
+            return Err(ApplyError::InvalidTransaction("You are not allowed to sell someone else's account !".to_string()));
+        }
+
+        let price = match name_state.get_offer(&buyer) {
+            Some(price) => price,
+            None => return Err(ApplyError::InvalidTransaction(format!("No offer from {} for {}", buyer, username)))
+        };
+
+        OSCashierHandler::sell_name(name_state, &buyer, price, &payout, &signerkey, state)
+    }
+
+    // Sets (doesn't add to) the allowance of `spender`, 0 works same as Revoke
+    pub fn approve(&self, signerkey: String, payload: &OSCashierPayload, state: &mut OSCashierState) -> Result<(),ApplyError> {
+        let username = payload.get_name();
+        let spender = payload.get_spender();
+        let amount = payload.get_amount();
+
+        if !amount.is_finite() || amount < 0.0 {
+            return Err(ApplyError::InvalidTransaction(format!("Invalid allowance: {}", amount)))
+        }
+        if spender == username {
+            return Err(ApplyError::InvalidTransaction("Can't give an allowance to the same account".to_string()))
+        }
+
+        OSCashierHandler::check_exists(&username, state, "User doesn't exist")?;
+        OSCashierHandler::check_exists(&spender, state, "Spending user doesn't exist")?;
+
+        let mut internal_state = OSCashierHandler::load_state(&username, state)?;
+
+        OSCashierHandler::reject_if_multisig(&internal_state)?;
+
+        if internal_state.get_key() != signerkey {
+            return Err(ApplyError::InvalidTransaction("You are not allowed to give allowances from someone else's account !".to_string()));
+        }
+
+        internal_state.set_allowance(spender, amount);
+        OSCashierHandler::touch(&mut internal_state, state)?;
+
+        OSCashierHandler::save_state(&username, internal_state, state)
+    }
+
+    pub fn revoke(&self, signerkey: String, payload: &OSCashierPayload, state: &mut OSCashierState) -> Result<(),ApplyError> {
+        let username = payload.get_name();
+
+        OSCashierHandler::check_exists(&username, state, "User doesn't exist")?;
+        let mut internal_state = OSCashierHandler::load_state(&username, state)?;
+
+        if internal_state.get_key() != signerkey {
+            return Err(ApplyError::InvalidTransaction("You are not allowed to revoke allowances of someone else's account !".to_string()));
+        }
+
+        internal_state.set_allowance(payload.get_spender(), 0.0);
+        OSCashierHandler::touch(&mut internal_state, state)?;
+
+        OSCashierHandler::save_state(&username, internal_state, state)
+    }
+
+    pub fn transfer_from(&self, signerkey: String, payload: &OSCashierPayload, state: &mut OSCashierState) -> Result<(),ApplyError> {
+        let username = payload.get_name();
+        let spender = payload.get_spender();
+        let receiver = payload.get_receiver();
+        let transaction_amount = payload.get_amount();
+
+        if !transaction_amount.is_finite() || transaction_amount <= 0.0 {
+            return Err(ApplyError::InvalidTransaction("Amount must be positive".to_string()))
+        }
+        if receiver == username {
+            return Err(ApplyError::InvalidTransaction("Can't transfer to the same account".to_string()))
+        }
+
+        OSCashierHandler::check_exists(&username, state, "Sending user doesn't exist")?;
+        OSCashierHandler::check_exists(&spender, state, "Spending user doesn't exist")?;
+        OSCashierHandler::check_exists(&receiver, state, "Receiving user doesn't exist")?;
+
+        if OSCashierHandler::load_state(&spender, state)?.get_key() != signerkey {
+            return Err(ApplyError::InvalidTransaction(format!("You can't spend as {} !", spender)))
+        }
+
+        let mut payer_state = OSCashierHandler::load_state(&username, state)?;
+
+        OSCashierHandler::reject_if_multisig(&payer_state)?;
+        OSCashierHandler::check_transfer_extras(payload, &mut payer_state, state)?;
+
+        if payer_state.spend_allowance(&spender, transaction_amount).is_err() {
+            return Err(ApplyError::InvalidTransaction(format!(
+                "{} may spend {} of {}'s {} points, not {}",
+                spender, payer_state.get_allowance(&spender), username, payer_state.get_points(), transaction_amount
+            )))
+        }
+        OSCashierHandler::save_state(&username, payer_state, state)?;
+
+        let mut receiver_state = OSCashierHandler::load_state(&receiver, state)?;
+        if receiver_state.add_points(transaction_amount).is_err() {
+            return Err(ApplyError::InvalidTransaction( format!("Couldn't Increment points of {}", receiver) ))
+        }
+        OSCashierHandler::save_state(&receiver, receiver_state, state)
+    }
+
+    // All or nothing, a single missing receiver or a short balance rejects the whole payout
+    pub fn multi_transfer(&self, signerkey: String, payload: &OSCashierPayload, state: &mut OSCashierState) -> Result<(),ApplyError> {
+        let username = payload.get_name();
+        let recipients = payload.get_recipients();
+
+        if recipients.is_empty() || recipients.len() > MAX_RECIPIENTS {
+            return Err(ApplyError::InvalidTransaction(format!("A multi transfer needs 1 to {} receivers", MAX_RECIPIENTS)))
+        }
+        if recipients.contains_key(&username) {
+            return Err(ApplyError::InvalidTransaction("Can't transfer to the same account".to_string()))
+        }
+        if recipients.values().any(|amount| !(*amount > 0.0)) {
+            return Err(ApplyError::InvalidTransaction("Every amount must be positive".to_string()))
+        }
+
+        OSCashierHandler::check_exists(&username, state, "Sending user doesn't exist")?;
+        let mut payer_state = OSCashierHandler::load_state(&username, state)?;
+
+        OSCashierHandler::reject_if_multisig(&payer_state)?;
+
+        if payer_state.get_key() != signerkey {
+            return Err(ApplyError::InvalidTransaction("You are not allowed to transfer someone else's points !".to_string()));
+        }
+
+        OSCashierHandler::check_transfer_extras(payload, &mut payer_state, state)?;
+
+        let total: f32 = recipients.values().sum();
+        if payer_state.get_points() < total as f64 {
+            return Err(ApplyError::InvalidTransaction(format!("{} can't pay {} in total", username, total)))
+        }
+        if payer_state.dec_points(total).is_err() {
+            return Err(ApplyError::InvalidTransaction( format!("Couldn't Decrement points from {}", username) ))
+        }
+        OSCashierHandler::touch(&mut payer_state, state)?;
+
+        for (receiver, amount) in recipients.iter() {
+            OSCashierHandler::check_exists(receiver, state, &format!("Receiving user {} doesn't exist", receiver))?;
+
+            let mut receiver_state = OSCashierHandler::load_state(receiver, state)?;
+            if receiver_state.add_points(*amount).is_err() {
+                return Err(ApplyError::InvalidTransaction( format!("Couldn't Increment points of {}", receiver) ))
+            }
+            OSCashierHandler::save_state(receiver, receiver_state, state)?;
+        }
+
+        #[cfg(debug_assertions)]
+        println!("Multi transfer {} -> {:?} ({})", username, recipients, payload.get_memo());
+
+        OSCashierHandler::save_state(&username, payer_state, state)
+    }
+
+    // First payment falls due one interval after creation
+    pub fn create_schedule(&self, signerkey: String, payload: &OSCashierPayload, state: &mut OSCashierState) -> Result<(),ApplyError> {
+        let username = payload.get_name();
+        let receiver = payload.get_receiver();
+        let amount = payload.get_amount();
+
+        if username == receiver {
+            return Err(ApplyError::InvalidTransaction("Can't schedule a transfer to the same account".to_string()))
+        }
+        if !(amount > 0.0) {
+            return Err(ApplyError::InvalidTransaction("Amount must be positive".to_string()))
+        }
+        if payload.get_interval() == 0 {
+            return Err(ApplyError::InvalidTransaction("Interval must be at least a second".to_string()))
+        }
+
+        OSCashierHandler::check_exists(&username, state, "Sending user doesn't exist")?;
+        OSCashierHandler::check_exists(&receiver, state, "Receiving user doesn't exist")?;
+        let mut internal_state = OSCashierHandler::load_state(&username, state)?;
+
+        OSCashierHandler::reject_if_multisig(&internal_state)?;
+
+        if internal_state.get_key() != signerkey {
+            return Err(ApplyError::InvalidTransaction("You are not allowed to schedule transfers from someone else's account !".to_string()));
+        }
+
+        let now = OSCashierHandler::get_chain_time(state)?;
+        let end_at = match payload.get_end_at() {
+            0 => None,
+            end_at if end_at <= now => return Err(ApplyError::InvalidTransaction("Schedule would end before its first payment".to_string())),
+            end_at => Some(end_at)
+        };
+        let runs = match payload.get_runs() {
+            0 => None,
+            runs => Some(runs)
+        };
+
+        let schedule_id = internal_state.add_schedule(Schedule::new(receiver, amount, payload.get_interval(), now + payload.get_interval(), runs, end_at));
+        internal_state.touch(now);
+
+        #[cfg(debug_assertions)]
+        println!("Schedule #{} created for {}", schedule_id, username);
+
+        OSCashierHandler::save_state(&username, internal_state, state)
+    }
+
+    pub fn cancel_schedule(&self, signerkey: String, payload: &OSCashierPayload, state: &mut OSCashierState) -> Result<(),ApplyError> {
+        let username = payload.get_name();
+
+        OSCashierHandler::check_exists(&username, state, "User doesn't exist")?;
+        let mut internal_state = OSCashierHandler::load_state(&username, state)?;
+
+        if internal_state.get_key() != signerkey {
+            return Err(ApplyError::InvalidTransaction("You are not allowed to cancel someone else's schedules !".to_string()));
+        }
+
+        if internal_state.remove_schedule(payload.get_schedule_id()).is_none() {
+            return Err(ApplyError::InvalidTransaction(format!("No schedule #{} on {}", payload.get_schedule_id(), username)))
+        }
+        OSCashierHandler::touch(&mut internal_state, state)?;
+
+        OSCashierHandler::save_state(&username, internal_state, state)
+    }
+
+    /*
+     * Anyone may submit this, pays each due schedule of `name` once (executing again catches up missed intervals)
+     *
+     * A schedule the payer can't currently afford stays due, one whose receiver is gone is dropped
+     */
+    pub fn execute_due(&self, _signerkey: String, payload: &OSCashierPayload, state: &mut OSCashierState) -> Result<(),ApplyError> {
+        let username = payload.get_name();
+
+        OSCashierHandler::check_exists(&username, state, "User doesn't exist")?;
+        let mut payer_state = OSCashierHandler::load_state(&username, state)?;
+
+        let now = OSCashierHandler::get_chain_time(state)?;
+        let mut executed = 0;
+        for schedule_id in payer_state.get_due_schedule_ids(now) {
+            let (receiver, amount) = match payer_state.get_schedule_mut(schedule_id) {
+                Some(schedule) => (schedule.get_receiver(), schedule.get_amount()),
+                None => continue
+            };
+
+            match state.does_entry_exist(&receiver) {
+                Ok(true) => {},
+                Ok(false) => {
+                    payer_state.remove_schedule(schedule_id);
+                    executed += 1;
+                    continue
+                },
+                Err(context_error) => return Err(ApplyError::InternalError(format!(
+                    "ContextError: {}", context_error.to_string()
+                )))
+            }
+
+            if payer_state.get_points() < amount as f64 {
+                continue
+            }
+            if payer_state.dec_points(amount).is_err() {
+                return Err(ApplyError::InvalidTransaction( format!("Couldn't Decrement points from {}", username) ))
+            }
+
+            let mut receiver_state = OSCashierHandler::load_state(&receiver, state)?;
+            if receiver_state.add_points(amount).is_err() {
+                return Err(ApplyError::InvalidTransaction( format!("Couldn't Increment points of {}", receiver) ))
+            }
+            OSCashierHandler::save_state(&receiver, receiver_state, state)?;
+
+            let finished = match payer_state.get_schedule_mut(schedule_id) {
+                Some(schedule) => {
+                    schedule.advance();
+                    schedule.is_finished()
+                },
+                None => false
+            };
+            if finished {
+                payer_state.remove_schedule(schedule_id);
+            }
+            executed += 1;
+
+            #[cfg(debug_assertions)]
+            println!("Schedule #{}: {} -> {} ({})", schedule_id, username, receiver, amount);
+        }
+
+        if executed == 0 {
+            return Err(ApplyError::InvalidTransaction(format!("Nothing payable is due on {}", username)))
+        }
+
+        OSCashierHandler::save_state(&username, payer_state, state)
+    }
+
+    // Needs an arbiter, a condition, or both
+    pub fn create_escrow(&self, signerkey: String, payload: &OSCashierPayload, state: &mut OSCashierState) -> Result<(),ApplyError> {
+        let username = payload.get_name();
+        let beneficiary = payload.get_receiver();
+        let amount = payload.get_amount();
+        let arbiter = payload.get_arbiter();
+        let module = payload.get_module_name();
+
+        if username == beneficiary {
+            return Err(ApplyError::InvalidTransaction("Can't escrow to the same account".to_string()))
+        }
+        if !(amount > 0.0) {
+            return Err(ApplyError::InvalidTransaction("Amount must be positive".to_string()))
+        }
+        if arbiter.is_empty() && module.is_empty() {
+            return Err(ApplyError::InvalidTransaction("An escrow needs an arbiter or a condition".to_string()))
+        }
+        if !arbiter.is_empty() && !OSCashierHandler::is_valid_public_key(&arbiter) {
+            return Err(ApplyError::InvalidTransaction("Arbiter is not a valid public key".to_string()))
+        }
+
+        OSCashierHandler::check_exists(&username, state, "Sending user doesn't exist")?;
+        OSCashierHandler::check_exists(&beneficiary, state, "Beneficiary doesn't exist")?;
+        let mut internal_state = OSCashierHandler::load_state(&username, state)?;
+
+        OSCashierHandler::reject_if_multisig(&internal_state)?;
+
+        if internal_state.get_key() != signerkey {
+            return Err(ApplyError::InvalidTransaction("You are not allowed to escrow someone else's points !".to_string()));
+        }
+        if !module.is_empty() && internal_state.get_module_rating(&module).is_none() {
+            return Err(ApplyError::InvalidTransaction(format!("No such module \"{}\"", module)))
+        }
+
+        let now = OSCashierHandler::get_chain_time(state)?;
+        if payload.get_deadline() <= now {
+            return Err(ApplyError::InvalidTransaction("Deadline must be in the future".to_string()))
+        }
+
+        if internal_state.get_points() < amount as f64 {
+            return Err(ApplyError::InvalidTransaction(format!("{} doesn't have {} to escrow", username, amount)))
+        }
+        if internal_state.dec_points(amount).is_err() {
+            return Err(ApplyError::InvalidTransaction( format!("Couldn't Decrement points from {}", username) ))
+        }
+
+        let escrow = Escrow::new(
+            beneficiary,
+            amount,
+            if arbiter.is_empty() { None } else { Some(arbiter) },
+            if module.is_empty() { None } else { Some(EscrowCondition::new(module, payload.get_min_plugged())) },
+            payload.get_deadline()
+        );
+        let escrow_id = internal_state.add_escrow(escrow);
+        internal_state.touch(now);
+
+        #[cfg(debug_assertions)]
+        println!("Escrow #{} created by {} for {}", escrow_id, username, amount);
+
+        OSCashierHandler::save_state(&username, internal_state, state)
+    }
+
+    // The payer or the arbiter can release at any time, anyone can once the condition holds, until the deadline
+    pub fn release_escrow(&self, signerkey: String, payload: &OSCashierPayload, state: &mut OSCashierState) -> Result<(),ApplyError> {
+        let username = payload.get_name();
+        let escrow_id = payload.get_escrow_id();
+
+        OSCashierHandler::check_exists(&username, state, "User doesn't exist")?;
+        let mut payer_state = OSCashierHandler::load_state(&username, state)?;
+
+        let (beneficiary, amount) = match payer_state.get_escrow(escrow_id) {
+            Some(escrow) => (escrow.get_beneficiary(), escrow.get_amount()),
+            None => return Err(ApplyError::InvalidTransaction(format!("No escrow #{} on {}", escrow_id, username)))
+        };
+
+        OSCashierHandler::check_exists(&beneficiary, state, "Beneficiary doesn't exist anymore, the escrow can only be refunded")?;
+        let mut beneficiary_state = OSCashierHandler::load_state(&beneficiary, state)?;
+
+        let authorised = match payer_state.get_escrow(escrow_id) {
+            Some(escrow) if signerkey == payer_state.get_key() || escrow.is_arbiter(&signerkey) => true,
+            Some(escrow) => match escrow.get_condition() {
+                Some(condition) => {
+                    let now = OSCashierHandler::get_chain_time(state)?;
+                    !escrow.is_expired(now) && condition.is_met(beneficiary_state.get_plugged_at(&condition.get_module()), now)
+                },
+                None => false
+            },
+            None => false
+        };
+        if !authorised {
+            return Err(ApplyError::InvalidTransaction(format!("Escrow #{} of {} can't be released yet", escrow_id, username)))
+        }
+
+        if beneficiary_state.add_points(amount).is_err() {
+            return Err(ApplyError::InvalidTransaction( format!("Couldn't Increment points of {}", beneficiary) ))
+        }
+        payer_state.remove_escrow(escrow_id);
+        if signerkey == payer_state.get_key() {
+            OSCashierHandler::touch(&mut payer_state, state)?;
+        }
+
+        OSCashierHandler::save_state(&beneficiary, beneficiary_state, state)?;
+        OSCashierHandler::save_state(&username, payer_state, state)
+    }
+
+    // The arbiter or the beneficiary can refund at any time, anyone can after the deadline
+    pub fn refund_escrow(&self, signerkey: String, payload: &OSCashierPayload, state: &mut OSCashierState) -> Result<(),ApplyError> {
+        let username = payload.get_name();
+        let escrow_id = payload.get_escrow_id();
+
+        OSCashierHandler::check_exists(&username, state, "User doesn't exist")?;
+        let mut payer_state = OSCashierHandler::load_state(&username, state)?;
+
+        let (beneficiary, amount) = match payer_state.get_escrow(escrow_id) {
+            Some(escrow) => (escrow.get_beneficiary(), escrow.get_amount()),
+            None => return Err(ApplyError::InvalidTransaction(format!("No escrow #{} on {}", escrow_id, username)))
+        };
+
+        let is_beneficiary = match state.does_entry_exist(&beneficiary) {
+            Ok(true) => OSCashierHandler::load_state(&beneficiary, state)?.get_key() == signerkey,
+            Ok(false) => false,
+            Err(context_error) => return Err(ApplyError::InternalError(format!(
+                "ContextError: {}", context_error.to_string()
+            )))
+        };
+
+        let authorised = match payer_state.get_escrow(escrow_id) {
+            Some(escrow) if is_beneficiary || escrow.is_arbiter(&signerkey) => true,
+            Some(escrow) => escrow.is_expired(OSCashierHandler::get_chain_time(state)?),
+            None => false
+        };
+        if !authorised {
+            return Err(ApplyError::InvalidTransaction(format!("Escrow #{} of {} can't be refunded before its deadline", escrow_id, username)))
+        }
+
+        if payer_state.add_points(amount).is_err() {
+            return Err(ApplyError::InvalidTransaction( format!("Couldn't Increment points of {}", username) ))
+        }
+        payer_state.remove_escrow(escrow_id);
+
+        OSCashierHandler::save_state(&username, payer_state, state)
+    }
+
+    // The owner manages everyone, admins manage members and viewers only
+    pub fn set_member(&self, signerkey: String, payload: &OSCashierPayload, state: &mut OSCashierState) -> Result<(),ApplyError> {
+        let username = payload.get_name();
+        let member_key = payload.get_public_key();
+
+        let role = match OrgRole::from_string(&payload.get_role()) {
+            Some(role) => role,
+            None => return Err(ApplyError::InvalidTransaction(format!("Unknown role \"{}\", use admin, member or viewer", payload.get_role())))
+        };
+        if !OSCashierHandler::is_valid_public_key(&member_key) {
+            return Err(ApplyError::InvalidTransaction(format!("Invalid public key: \"{}\"", member_key)))
+        }
+        if !payload.get_amount().is_finite() || payload.get_amount() < 0.0 {
+            return Err(ApplyError::InvalidTransaction(format!("Invalid budget: {}", payload.get_amount())))
+        }
+
+        OSCashierHandler::check_exists(&username, state, "User doesn't exist")?;
+        let mut internal_state = OSCashierHandler::load_state(&username, state)?;
+
+        OSCashierHandler::reject_if_multisig(&internal_state)?;
+
+        if member_key == internal_state.get_key() {
+            return Err(ApplyError::InvalidTransaction("The owner can't also be a member".to_string()))
+        }
+
+        let is_owner = internal_state.get_key() == signerkey;
+        let is_admin = internal_state.get_member_role(&signerkey) == Some(OrgRole::Admin);
+        let touches_admin = role == OrgRole::Admin || internal_state.get_member_role(&member_key) == Some(OrgRole::Admin);
+        if !(is_owner || (is_admin && !touches_admin)) {
+            return Err(ApplyError::InvalidTransaction(format!("You are not allowed to manage this member of {} !", username)));
+        }
+
+        internal_state.set_member(member_key, role, payload.get_amount());
+        OSCashierHandler::touch(&mut internal_state, state)?;
+
+        OSCashierHandler::save_state(&username, internal_state, state)
+    }
+
+    // Same rules as set_member, and anyone may leave
+    pub fn remove_member(&self, signerkey: String, payload: &OSCashierPayload, state: &mut OSCashierState) -> Result<(),ApplyError> {
+        let username = payload.get_name();
+        let member_key = payload.get_public_key();
+
+        OSCashierHandler::check_exists(&username, state, "User doesn't exist")?;
+        let mut internal_state = OSCashierHandler::load_state(&username, state)?;
+
+        OSCashierHandler::reject_if_multisig(&internal_state)?;
+
+        let is_owner = internal_state.get_key() == signerkey;
+        let is_admin = internal_state.get_member_role(&signerkey) == Some(OrgRole::Admin);
+        let touches_admin = internal_state.get_member_role(&member_key) == Some(OrgRole::Admin);
+        if !(is_owner || member_key == signerkey || (is_admin && !touches_admin)) {
+            return Err(ApplyError::InvalidTransaction(format!("You are not allowed to remove this member of {} !", username)));
+        }
+
+        if !internal_state.remove_member(&member_key) {
+            return Err(ApplyError::InvalidTransaction(format!("{} is not a member of {}", member_key, username)))
+        }
+        // A member leaving isn't the organisation being used
+        if is_owner || is_admin {
+            OSCashierHandler::touch(&mut internal_state, state)?;
+        }
+
+        OSCashierHandler::save_state(&username, internal_state, state)
+    }
+
+    pub fn transfer(&self, signerkey: String, payload: &OSCashierPayload, state: &mut OSCashierState) -> Result<(),ApplyError> {
+        let username = payload.get_name();
+        let receiver = payload.get_receiver();
+
+        // Check whether payer exists
+        match state.does_entry_exist(&username) {
+            Ok(exists) => {
+                if !exists {
+                    return Err(ApplyError::InvalidTransaction("Sending user doesn't exist".to_string()))
+                }
+            },
+            Err(e) => {
+                return Err(ApplyError::InternalError(format!(
+                    "ContextError: {}", e.to_string()
+                )));
+            }
+        };
+        // Check whether receiver exists
+        match state.does_entry_exist(&receiver) {
+            Ok(exists) => {
+                if !exists {
+                    return Err(ApplyError::InvalidTransaction("Receiving user doesn't exist".to_string()))
+                }
+            },
+            Err(e) => {
+                return Err(ApplyError::InternalError(format!(
+                    "ContextError: {}", e.to_string()
+                )));
+            }
+        };
+
+        let mut payer_state = match state.get_state(username.clone(), signerkey.clone()) {
+            Ok(state) => state,
+            Err(context_error) => return Err(ApplyError::InternalError(format!(
+                "ContextError: {}", context_error.to_string()
+            )))
+        };
+        let mut receiver_state = match state.get_state(receiver.clone(), signerkey.clone()) {     // TODO: It's public key won't be same and we don't care either
+            Ok(state) => state,
+            Err(context_error) => return Err(ApplyError::InternalError(format!(
+                "ContextError: {}", context_error.to_string()
+            )))
+        };
+
+        OSCashierHandler::reject_if_multisig(&payer_state)?;
+
+        let payer_public_key = payer_state.get_key();
+
+        if payer_public_key != signerkey {
+            return Err(ApplyError::InvalidTransaction("You are not allowed to transfer someone else's points !".to_string()));
+        };
+
+        OSCashierHandler::check_transfer_extras(payload, &mut payer_state, state)?;
+        OSCashierHandler::touch(&mut payer_state, state)?;
+
+        #[cfg(debug_assertions)]
+        println!("Transfer {} -> {}: {} ({})", username, receiver, payload.get_amount().abs(), payload.get_memo());
 
         // The below are temporary, and in memory only, we will decrease balance, only when the payment was successful
         let transaction_amount = payload.get_amount().abs();    // Only positive amounts accepted
@@ -297,7 +1959,33 @@ impl TransactionHandler for OSCashierHandler {
                 Actions::Register => self.register(public_key, &payload, &mut state),
                 Actions::PlugMod => self.plug_module(public_key, &payload, &mut state),
                 Actions::UnplugMod => self.unplug_module(public_key, &payload, &mut state),
-                Actions::Transfer => self.transfer(public_key, &payload, &mut state)
+                Actions::Transfer => self.transfer(public_key, &payload, &mut state),
+                Actions::Claim => self.claim(public_key, &payload, &mut state),
+                Actions::Reboot => self.reboot(public_key, &payload, &mut state),
+                Actions::RotateKey => self.rotate_key(public_key, &payload, &mut state),
+                Actions::SetRecovery => self.set_recovery(public_key, &payload, &mut state),
+                Actions::SetSigners => self.set_signers(public_key, &payload, &mut state),
+                Actions::Propose => self.propose(public_key, &payload, &mut state),
+                Actions::ApproveProposal => self.approve_proposal(public_key, &payload, &mut state),
+                Actions::Deregister => self.deregister(public_key, &payload, &mut state),
+                Actions::Reap => self.reap(public_key, &payload, &mut state),
+                Actions::TransferName => self.transfer_name(public_key, &payload, &mut state),
+                Actions::ListName => self.list_name(public_key, &payload, &mut state),
+                Actions::BuyName => self.buy_name(public_key, &payload, &mut state),
+                Actions::OfferName => self.offer_name(public_key, &payload, &mut state),
+                Actions::AcceptOffer => self.accept_offer(public_key, &payload, &mut state),
+                Actions::Approve => self.approve(public_key, &payload, &mut state),
+                Actions::Revoke => self.revoke(public_key, &payload, &mut state),
+                Actions::TransferFrom => self.transfer_from(public_key, &payload, &mut state),
+                Actions::MultiTransfer => self.multi_transfer(public_key, &payload, &mut state),
+                Actions::CreateSchedule => self.create_schedule(public_key, &payload, &mut state),
+                Actions::CancelSchedule => self.cancel_schedule(public_key, &payload, &mut state),
+                Actions::ExecuteDue => self.execute_due(public_key, &payload, &mut state),
+                Actions::CreateEscrow => self.create_escrow(public_key, &payload, &mut state),
+                Actions::ReleaseEscrow => self.release_escrow(public_key, &payload, &mut state),
+                Actions::RefundEscrow => self.refund_escrow(public_key, &payload, &mut state),
+                Actions::SetMember => self.set_member(public_key, &payload, &mut state),
+                Actions::RemoveMember => self.remove_member(public_key, &payload, &mut state)
             },
             None => {
                 Err(ApplyError::InvalidTransaction("Unsupported Action specified".to_string()))
@@ -16,6 +16,8 @@ use sawtooth_sdk::processor::TransactionProcessor;
 mod structs;
 mod handler;
 mod payload_impl;
+mod username;
+mod pow;
 use handler::OSCashierHandler;
 
 fn main() {
@@ -1,4 +1,4 @@
-pub use crate::structs::payload::{OSCashierPayload,Actions};
+pub use crate::structs::payload::{OSCashierPayload,Actions,MAX_MEMO_LENGTH,MAX_RECIPIENTS};
 use sawtooth_sdk::processor::handler::ApplyError;
 
 impl OSCashierPayload {
@@ -0,0 +1 @@
+../../client/src/pow.rs
\ No newline at end of file
@@ -0,0 +1 @@
+../../../client/src/escrow.rs
\ No newline at end of file
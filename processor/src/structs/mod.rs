@@ -1,3 +1,6 @@
 extern crate serde_cbor;
 pub mod state;
 pub mod payload;
+pub mod proposal;
+pub mod schedule;
+pub mod escrow;
@@ -0,0 +1 @@
+../../../client/src/proposal.rs
\ No newline at end of file
@@ -0,0 +1 @@
+../../../client/src/schedule.rs
\ No newline at end of file
@@ -1,7 +1,15 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::collections::btree_map::Entry;
 use serde_derive::{Serialize, Deserialize};
 use sawtooth_sdk::processor::handler::{TransactionContext, ContextError};
+use sawtooth_sdk::messages::setting::Setting;
+use sawtooth_sdk::messages::block_info::{BlockInfo, BlockInfoConfig};
+use protobuf::Message;
+
+use super::payload::OSCashierPayload;
+use super::proposal::Proposal;
+use super::schedule::Schedule;
+use super::escrow::Escrow;
 
 mod util {
     pub fn get_timestamp_sec() -> u64 {
@@ -9,15 +17,102 @@ mod util {
     }
 }
 
+#[derive(Debug,Serialize,Deserialize)]
+pub struct PendingRecovery {
+    new_key: String,
+    requested_at: u64
+}
+
+#[derive(Debug,Serialize,Deserialize)]
+pub struct Listing {
+    price: f32,
+    payout: String  // account receiving the price
+}
+
+impl Listing {
+    pub fn new(price: f32, payout: String) -> Listing {
+        Listing { price, payout }
+    }
+
+    pub fn get_price(&self) -> f32 {
+        self.price
+    }
+
+    pub fn get_payout(&self) -> String {
+        self.payout.clone()
+    }
+}
+
+#[derive(Debug,Clone,Copy,PartialEq,Serialize,Deserialize)]
+pub enum OrgRole {
+    Admin,  // manages members and viewers, plugs without a budget
+    Member, // plugs within its budget
+    Viewer
+}
+
+impl OrgRole {
+    pub fn from_string(role: &str) -> Option<OrgRole> {
+        match role.to_lowercase().as_str() {
+            "admin" => Some(OrgRole::Admin),
+            "member" => Some(OrgRole::Member),
+            "viewer" => Some(OrgRole::Viewer),
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug,Serialize,Deserialize)]
+pub struct OrgMember {
+    role: OrgRole,
+    budget: f32     // points a Member may still spend plugging modules
+}
+
 #[derive(Debug,Serialize,Deserialize)]
 pub struct _InternalOSCashierState {
     name: String,
     key: String,    // public key
     points: f64,
     mods: BTreeMap<String,u64>, // {str, timepoint}, timepoint is "unix timestamp", and in seconds
+    // Newer fields are defaulted, so that accounts written by older versions still decode
+    #[serde(default)]
+    recovery_key: String,   // empty if not set
+    #[serde(default)]
+    recovery_timelock: u64, // seconds the recovery key has to wait before it can rotate
+    #[serde(default)]
+    pending_recovery: Option<PendingRecovery>,
+    #[serde(default)]
+    signers: Vec<String>,   // multisig keys, empty for a normal single key account
+    #[serde(default)]
+    threshold: u32,         // approvals required out of `signers`
+    #[serde(default)]
+    proposals: BTreeMap<u64,Proposal>,  // pending multisig operations, by proposal id
+    #[serde(default)]
+    next_proposal_id: u64,
+    #[serde(default)]
+    last_active: u64,   // block timestamp of the last operation signed for this account, 0 till it's first written with block info on chain
+    #[serde(default)]
+    listing: Option<Listing>,       // set when the account is up for sale
+    #[serde(default)]
+    offers: BTreeMap<String,f32>,   // {buyer account, price} offered for this account
+    #[serde(default)]
+    allowances: BTreeMap<String,f32>,   // {spender account, points it may still move out of this account}
+    #[serde(default)]
+    idempotency_keys: BTreeMap<String,u64>, // {key, timestamp} of recent transfers out of this account
+    #[serde(default)]
+    schedules: BTreeMap<u64,Schedule>,  // standing orders paying out of this account, by id
+    #[serde(default)]
+    next_schedule_id: u64,
+    #[serde(default)]
+    escrows: BTreeMap<u64,Escrow>,  // points locked out of this account's balance, by id
+    #[serde(default)]
+    next_escrow_id: u64,
+    #[serde(default)]
+    members: BTreeMap<String,OrgMember>, // {public key, role}, an account with members is an organisation
+    #[serde(default)]
+    plugged_at: BTreeMap<String,u64>    // {module, block timestamp it was plugged at}, escrow conditions go by it, missing if plugged without block info
 }
 
-const DEFAULT_INIT_POINTS: f32 = 10.0;
+pub const DEFAULT_INIT_POINTS: f32 = 10.0;
 const COIN_MULTIPLIER: f32 = 0.05;
 impl _InternalOSCashierState {
     pub fn new(username: String, publickey: String) -> _InternalOSCashierState {
@@ -25,10 +120,35 @@ impl _InternalOSCashierState {
             name: username,
             key: publickey,
             points: DEFAULT_INIT_POINTS as f64,
-            mods: BTreeMap::new()
+            mods: BTreeMap::new(),
+            recovery_key: String::new(),
+            recovery_timelock: 0,
+            pending_recovery: None,
+            signers: Vec::new(),
+            threshold: 0,
+            proposals: BTreeMap::new(),
+            next_proposal_id: 0,
+            last_active: 0,
+            listing: None,
+            offers: BTreeMap::new(),
+            allowances: BTreeMap::new(),
+            idempotency_keys: BTreeMap::new(),
+            schedules: BTreeMap::new(),
+            next_schedule_id: 0,
+            escrows: BTreeMap::new(),
+            next_escrow_id: 0,
+            members: BTreeMap::new(),
+            plugged_at: BTreeMap::new()
         }
     }
 
+    // System accounts (eg. the treasury) start empty, and have no key that could sign for them
+    pub fn new_system(name: String) -> _InternalOSCashierState {
+        let mut system_state = _InternalOSCashierState::new(name, String::new());
+        system_state.points = 0.0;
+        system_state
+    }
+
     pub fn from_bytes( state_bytes: &[u8] ) -> _InternalOSCashierState {
         serde_cbor::from_slice( state_bytes ).unwrap()
     }
@@ -45,10 +165,81 @@ impl _InternalOSCashierState {
         self.key.clone()
     }
 
+    // Any rotation cancels a pending recovery, the owner uses this to veto a recovery they didn't ask for
+    pub fn set_key(&mut self, publickey: String) {
+        self.key = publickey;
+        self.pending_recovery = None;
+    }
+
+    pub fn get_recovery_key(&self) -> String {
+        self.recovery_key.clone()
+    }
+
+    pub fn set_recovery(&mut self, recovery_key: String, timelock: u64) {
+        self.recovery_key = recovery_key;
+        self.recovery_timelock = timelock;
+        self.pending_recovery = None;
+    }
+
+    /*
+     * Rotation by the recovery key is two step: the first request starts the timelock, repeating the
+     * same request once it has passed rotates the key
+     *
+     * Ok(true) -> key rotated, Ok(false) -> timelock started, Err(secs) -> still locked for secs
+     */
+    pub fn recover_key(&mut self, new_key: String, now: u64) -> Result<bool,u64> {
+        let requested_at = match &self.pending_recovery {
+            Some(pending) if pending.new_key == new_key => pending.requested_at,
+            _ => {
+                self.pending_recovery = Some(PendingRecovery { new_key: new_key.clone(), requested_at: now });
+                now
+            }
+        };
+
+        let unlocks_at = requested_at + self.recovery_timelock;
+        if now >= unlocks_at {
+            self.set_key(new_key);
+            Ok(true)
+        } else if requested_at == now {
+            Ok(false)
+        } else {
+            Err(unlocks_at - now)
+        }
+    }
+
     pub fn get_points(&self) -> f64 {
         self.points
     }
 
+    pub fn set_points(&mut self, points: f64) {
+        self.points = points;
+    }
+
+    // Empties the balance, returning what was there
+    pub fn take_points(&mut self) -> f64 {
+        let points = self.points;
+        self.points = 0.0;
+        points
+    }
+
+    pub fn has_mods(&self) -> bool {
+        !self.mods.is_empty()
+    }
+
+    // `now` is the block timestamp, every validator has to agree on it
+    pub fn touch(&mut self, now: u64) {
+        self.last_active = now;
+    }
+
+    pub fn is_activity_tracked(&self) -> bool {
+        self.last_active != 0
+    }
+
+    // An account that isn't tracked yet is never dormant, its clock starts on its next write
+    pub fn is_dormant(&self, period: u64, now: u64) -> bool {
+        self.is_activity_tracked() && now.saturating_sub(self.last_active) >= period
+    }
+
     pub fn add_points(&mut self, points: f32) -> Result<(),()> {
         self.points += points as f64;
         Ok(())
@@ -60,6 +251,26 @@ impl _InternalOSCashierState {
         Ok(())
     }
 
+    // Err if `key` was already used within the last `window` seconds, keys older than that are forgotten
+    //
+    // `now` is the block timestamp, every validator has to agree on what is a duplicate
+    pub fn record_idempotency_key(&mut self, key: String, window: u64, now: u64) -> Result<(),()> {
+        self.idempotency_keys.retain(|_, used_at| now.saturating_sub(*used_at) < window);
+
+        match self.idempotency_keys.entry(key) {
+            Entry::Occupied(_) => Err(()),
+            Entry::Vacant(e) => {
+                e.insert(now);
+                Ok(())
+            }
+        }
+    }
+
+    // Block timestamp the module was plugged at (claiming doesn't change it)
+    pub fn get_plugged_at(&self, module_name: &str) -> Option<u64> {
+        self.plugged_at.get(module_name).copied()
+    }
+
     pub fn get_seconds_since_added(&self, module_name: &str) -> Result<u64,()> {
         let curr_timestamp = util::get_timestamp_sec();
 
@@ -69,7 +280,8 @@ impl _InternalOSCashierState {
         }
     }
 
-    pub fn add_mod(&mut self, module_name: String) -> Result<(),()> {
+    // `plugged_at` is the block timestamp, None without block info on chain
+    pub fn add_mod(&mut self, module_name: String, plugged_at: Option<u64>) -> Result<(),()> {
         let performance_benefit = match self.get_module_rating(&module_name) { // From previous check, we know it exists in the array
             Some(rating) => rating,
             None => {
@@ -84,34 +296,268 @@ impl _InternalOSCashierState {
         match self.mods.entry(module_name) {
             Entry::Occupied(_) => Err(()), // don't add if already present
             Entry::Vacant(e) => {
+                let module_name = e.key().clone();
                 e.insert(util::get_timestamp_sec());
+                if let Some(plugged_at) = plugged_at {
+                    self.plugged_at.insert(module_name, plugged_at);
+                }
                 self.dec_points(transaction_cost)
             }
         }
     }
 
-    pub fn remove_mod(&mut self, module_name: &str) -> Result<(),()> {
-        let time_diff = match self.get_seconds_since_added(module_name) {
-            Ok(diff) => diff,
-            Err(_) => {
-                return Err(())
-            }
+    // Reward accrued so far by a plugged module, can be negative for modules worse than average
+    pub fn get_accrued_points(&self, module_name: &str) -> Result<f32,()> {
+        let time_diff = self.get_seconds_since_added(module_name)?;
+        let performance_benefit = match self.get_module_rating(module_name) {
+            Some(rating) => rating,
+            None => return Err(())
         };
-        let performance_benefit = self.get_module_rating(module_name).expect("[FATAL]: No such module exists, It must not even have been added !"); // From previous check, we know it exists in the array
 
-        let point_diff = COIN_MULTIPLIER * (time_diff as f32).sqrt() * performance_benefit;
+        Ok(COIN_MULTIPLIER * (time_diff as f32).sqrt() * performance_benefit)
+    }
+
+    fn settle_points(&mut self, point_diff: f32) -> Result<(),()> {
+        if point_diff < 0.0 {
+            self.dec_points(point_diff.abs())
+        } else {
+            self.add_points(point_diff)
+        }
+    }
+
+    // Returns the points settled on unplugging
+    pub fn remove_mod(&mut self, module_name: &str) -> Result<f32,()> {
+        let point_diff = self.get_accrued_points(module_name)?;
 
         match self.mods.remove(module_name) {
             Some(_) => {  // key was present and removed
-                if point_diff < 0.0 {
-                    self.dec_points(point_diff.abs())
-                } else {
-                    self.add_points(point_diff)
-                }
-            }
+                self.plugged_at.remove(module_name);
+                self.settle_points(point_diff)?;
+                Ok(point_diff)
+            },
             None => Err(()) // key not present
         }
     }
+
+    // Unplugs every module, returns {module, settled points} for each of them
+    pub fn remove_all_mods(&mut self) -> Result<BTreeMap<String,f32>,()> {
+        let module_names: Vec<String> = self.mods.keys().cloned().collect();
+        let mut breakdown = BTreeMap::new();
+
+        for module_name in module_names {
+            let point_diff = self.remove_mod(&module_name)?;
+            breakdown.insert(module_name, point_diff);
+        }
+
+        Ok(breakdown)
+    }
+
+    // Same settlement as remove_mod, but the module stays plugged and starts accruing again from now
+    pub fn claim_mod(&mut self, module_name: &str) -> Result<f32,()> {
+        let point_diff = self.get_accrued_points(module_name)?;
+
+        match self.mods.get_mut(module_name) {
+            Some(timestamp) => *timestamp = util::get_timestamp_sec(),
+            None => return Err(())
+        };
+
+        self.settle_points(point_diff)?;
+        Ok(point_diff)
+    }
+}
+
+// Multisig, once `signers` is set, transfers and plugs go through proposals approved by `threshold` of them
+impl _InternalOSCashierState {
+    pub fn is_multisig(&self) -> bool {
+        !self.signers.is_empty()
+    }
+
+    pub fn is_signer(&self, publickey: &str) -> bool {
+        self.signers.iter().any(|signer| signer == publickey)
+    }
+
+    // Pending proposals are dropped, approvals by the old signer set shouldn't count for the new one
+    pub fn set_signers(&mut self, signers: Vec<String>, threshold: u32) {
+        self.signers = signers;
+        self.threshold = threshold;
+        self.proposals.clear();
+    }
+
+    // The proposer's approval is counted right away, returns the new proposal's id
+    //
+    // `created_at` is the block timestamp, 0 without block info on chain
+    pub fn add_proposal(&mut self, operation: OSCashierPayload, proposer: String, created_at: u64) -> u64 {
+        let proposal_id = self.next_proposal_id;
+        let mut proposal = Proposal::new(operation, created_at);
+        proposal.approve(proposer);
+
+        self.proposals.insert(proposal_id, proposal);
+        self.next_proposal_id += 1;
+
+        proposal_id
+    }
+
+    pub fn get_proposal_mut(&mut self, proposal_id: u64) -> Option<&mut Proposal> {
+        self.proposals.get_mut(&proposal_id)
+    }
+
+    pub fn is_proposal_approved(&self, proposal_id: u64) -> bool {
+        match self.proposals.get(&proposal_id) {
+            Some(proposal) => {
+                let approvals = proposal.get_approvals().iter()
+                                        .filter(|key| self.is_signer(key))
+                                        .count();
+                approvals >= self.threshold as usize
+            },
+            None => false
+        }
+    }
+
+    pub fn remove_proposal(&mut self, proposal_id: u64) -> Option<Proposal> {
+        self.proposals.remove(&proposal_id)
+    }
+}
+
+// Allowances, other accounts allowed to spend from this one
+impl _InternalOSCashierState {
+    pub fn get_allowance(&self, spender: &str) -> f32 {
+        self.allowances.get(spender).copied().unwrap_or(0.0)
+    }
+
+    // Replaces any earlier allowance, 0 revokes it
+    pub fn set_allowance(&mut self, spender: String, amount: f32) {
+        if amount > 0.0 {
+            self.allowances.insert(spender, amount);
+        } else {
+            self.allowances.remove(&spender);
+        }
+    }
+
+    // Err if `amount` isn't a positive number, or is more than what's left of the allowance, or the balance
+    pub fn spend_allowance(&mut self, spender: &str, amount: f32) -> Result<(),()> {
+        let allowance = self.get_allowance(spender);
+        if !amount.is_finite() || amount <= 0.0 || amount > allowance || self.points < amount as f64 {
+            return Err(());
+        }
+
+        self.set_allowance(spender.to_string(), allowance - amount);
+        self.dec_points(amount)
+    }
+}
+
+// Standing orders, scheduled payments out of this account
+impl _InternalOSCashierState {
+    pub fn add_schedule(&mut self, schedule: Schedule) -> u64 {
+        let schedule_id = self.next_schedule_id;
+        self.schedules.insert(schedule_id, schedule);
+        self.next_schedule_id += 1;
+
+        schedule_id
+    }
+
+    pub fn remove_schedule(&mut self, schedule_id: u64) -> Option<Schedule> {
+        self.schedules.remove(&schedule_id)
+    }
+
+    pub fn get_schedule_mut(&mut self, schedule_id: u64) -> Option<&mut Schedule> {
+        self.schedules.get_mut(&schedule_id)
+    }
+
+    pub fn get_due_schedule_ids(&self, now: u64) -> Vec<u64> {
+        self.schedules.iter()
+                      .filter(|(_, schedule)| schedule.is_due(now))
+                      .map(|(schedule_id, _)| *schedule_id)
+                      .collect()
+    }
+}
+
+// Escrows, the locked points are already out of `points`
+impl _InternalOSCashierState {
+    pub fn add_escrow(&mut self, escrow: Escrow) -> u64 {
+        let escrow_id = self.next_escrow_id;
+        self.escrows.insert(escrow_id, escrow);
+        self.next_escrow_id += 1;
+
+        escrow_id
+    }
+
+    pub fn get_escrow(&self, escrow_id: u64) -> Option<&Escrow> {
+        self.escrows.get(&escrow_id)
+    }
+
+    pub fn remove_escrow(&mut self, escrow_id: u64) -> Option<Escrow> {
+        self.escrows.remove(&escrow_id)
+    }
+
+    pub fn has_escrows(&self) -> bool {
+        !self.escrows.is_empty()
+    }
+}
+
+// Organisations, other keys acting on this account by role, the owner `key` can do everything
+impl _InternalOSCashierState {
+    pub fn is_org(&self) -> bool {
+        !self.members.is_empty()
+    }
+
+    pub fn get_member_role(&self, key: &str) -> Option<OrgRole> {
+        self.members.get(key).map(|member| member.role)
+    }
+
+    // Replaces the role and budget if `key` is already a member
+    pub fn set_member(&mut self, key: String, role: OrgRole, budget: f32) {
+        self.members.insert(key, OrgMember { role, budget });
+    }
+
+    pub fn remove_member(&mut self, key: &str) -> bool {
+        self.members.remove(key).is_some()
+    }
+
+    pub fn spend_member_budget(&mut self, key: &str, amount: f32) -> Result<(),()> {
+        match self.members.get_mut(key) {
+            Some(member) if member.budget >= amount => {
+                member.budget -= amount;
+                Ok(())
+            },
+            _ => Err(())
+        }
+    }
+}
+
+// Selling the account (name, balance and modules) to another key
+impl _InternalOSCashierState {
+    // Everything tied to the old owner goes, the new owner sets up their own recovery/signers
+    pub fn transfer_ownership(&mut self, new_key: String) {
+        self.set_key(new_key);
+        self.set_recovery(String::new(), 0);
+        self.set_signers(Vec::new(), 0);
+        self.listing = None;
+        self.offers.clear();
+        self.allowances.clear();
+        self.schedules.clear();
+        self.members.clear();
+    }
+
+    pub fn get_listing(&self) -> Option<&Listing> {
+        self.listing.as_ref()
+    }
+
+    pub fn set_listing(&mut self, listing: Option<Listing>) {
+        self.listing = listing;
+    }
+
+    pub fn get_offer(&self, buyer: &str) -> Option<f32> {
+        self.offers.get(buyer).copied()
+    }
+
+    // A price of 0 withdraws the offer
+    pub fn set_offer(&mut self, buyer: String, price: f32) {
+        if price > 0.0 {
+            self.offers.insert(buyer, price);
+        } else {
+            self.offers.remove(&buyer);
+        }
+    }
 }
 
 // FUTURE: For now, it's here, in future remove it
@@ -128,12 +574,24 @@ impl _InternalOSCashierState {
     }
 }
 
+// Reverse index entry, accounts whose `key` is this public key
+#[derive(Debug,Serialize,Deserialize)]
+pub struct KeyIndex {
+    key: String,
+    accounts: BTreeSet<String>
+}
+
+// Account that receives swept balances, reserved for the OS
+pub const TREASURY_NAME: &str = "os";
+
 pub struct OSCashierState<'a> {
     context: &'a mut dyn TransactionContext,
     cache: BTreeMap<String,Vec<u8>> // this cache is valid, as only one tp is supposed to run for this os cashier version, no race problems, due to any other tp modifying it, rest internal race condition, we can always check later
 }
 
 const FAMILY_NAME: &str = "os-cashier";
+const SETTINGS_NAMESPACE: &str = "000000";
+const BLOCK_INFO_NAMESPACE: &str = "00b10c";
 impl<'a> OSCashierState<'a> {
     pub fn new( context: &'a mut dyn TransactionContext ) -> OSCashierState {
         OSCashierState {
@@ -173,10 +631,136 @@ impl<'a> OSCashierState<'a> {
         }
     }
 
-    pub fn set_state(&mut self, name: &str, updated_state: _InternalOSCashierState) -> Result<(),ContextError> {
+    /*
+     * Only starts the dormancy clock of accounts that aren't tracked yet, other writes (incoming transfers,
+     * offers, due schedules) aren't the owner's doing, the handlers touch the accounts their signer acts on
+     */
+    pub fn set_state(&mut self, name: &str, mut updated_state: _InternalOSCashierState) -> Result<(),ContextError> {
+        if !updated_state.is_activity_tracked() {
+            if let Some(now) = self.get_chain_timestamp()? {
+                updated_state.touch(now);
+            }
+        }
         self.context.set_state_entry(OSCashierState::get_address(name), updated_state.to_bytes())
     }
 
+    // "key-index:" can't be a username, so these never collide with account addresses
+    pub fn get_key_index_address(publickey: &str) -> String {
+        OSCashierState::get_address(&format!("key-index:{}", publickey))
+    }
+
+    pub fn get_key_accounts(&self, publickey: &str) -> Result<BTreeSet<String>, ContextError> {
+        match self.context.get_state_entry(&OSCashierState::get_key_index_address(publickey))? {
+            Some(index_bytes) => {
+                let index: KeyIndex = serde_cbor::from_slice(&index_bytes).expect("[KeyIndex] Couldn't deserialize key index");
+                Ok(index.accounts)
+            },
+            None => Ok(BTreeSet::new())
+        }
+    }
+
+    pub fn add_key_account(&mut self, publickey: &str, name: &str) -> Result<(),ContextError> {
+        if publickey.is_empty() {  // system accounts
+            return Ok(())
+        }
+
+        let mut accounts = self.get_key_accounts(publickey)?;
+        accounts.insert(name.to_string());
+        self.set_key_accounts(publickey, accounts)
+    }
+
+    pub fn remove_key_account(&mut self, publickey: &str, name: &str) -> Result<(),ContextError> {
+        if publickey.is_empty() {
+            return Ok(())
+        }
+
+        let mut accounts = self.get_key_accounts(publickey)?;
+        accounts.remove(name);
+        self.set_key_accounts(publickey, accounts)
+    }
+
+    fn set_key_accounts(&mut self, publickey: &str, accounts: BTreeSet<String>) -> Result<(),ContextError> {
+        let address = OSCashierState::get_key_index_address(publickey);
+
+        if accounts.is_empty() {
+            return self.context.delete_state_entry(&address).map(|_| ())
+        }
+
+        let index = KeyIndex { key: publickey.to_string(), accounts };
+        self.context.set_state_entry(address, serde_cbor::to_vec(&index).expect("[KeyIndex] Couldn't serialize key index"))
+    }
+
+    pub fn delete_state(&mut self, name: &str) -> Result<(),ContextError> {
+        self.context.delete_state_entry(&OSCashierState::get_address(name)).map(|_| ())
+    }
+
+    // The treasury account is created on its first credit
+    pub fn get_treasury_state(&self) -> Result<_InternalOSCashierState, ContextError> {
+        match self.context.get_state_entry(&OSCashierState::get_address(TREASURY_NAME))? {
+            Some(state_bytes) => Ok(_InternalOSCashierState::from_bytes(&state_bytes)),
+            None => Ok(_InternalOSCashierState::new_system(TREASURY_NAME.to_string()))
+        }
+    }
+
+    // Same scheme as the sawtooth settings family, key split on '.' into 4 parts, 16 hex chars of sha256 each
+    pub fn get_setting_address(key: &str) -> String {
+        let mut parts: Vec<&str> = key.splitn(4, '.').collect();
+        parts.resize(4, "");
+
+        parts.iter().fold(SETTINGS_NAMESPACE.to_string(), |address, part| {
+            address + &hex::encode( openssl::sha::sha256(part.as_bytes()) )[..16]
+        })
+    }
+
+    // On-chain configuration, set with `sawset proposal create`, None if not set
+    pub fn get_setting(&self, key: &str) -> Result<Option<String>, ContextError> {
+        let setting_bytes = match self.context.get_state_entry(&OSCashierState::get_setting_address(key))? {
+            Some(setting_bytes) => setting_bytes,
+            None => return Ok(None)
+        };
+
+        let setting = match Setting::parse_from_bytes(&setting_bytes) {
+            Ok(setting) => setting,
+            Err(e) => return Err(ContextError::SerializationError(Box::new(e)))
+        };
+
+        Ok(setting.get_entries()
+                  .iter()
+                  .find(|entry| entry.get_key() == key)
+                  .map(|entry| entry.get_value().to_string()))
+    }
+
+    /*
+     * Timestamp of the latest block, as recorded by the block info transaction family, None if it isn't running
+     *
+     * Unlike the processor's clock, every validator reads the same value here
+     */
+    pub fn get_chain_timestamp(&self) -> Result<Option<u64>, ContextError> {
+        let config_address = format!("{}01{}", BLOCK_INFO_NAMESPACE, "0".repeat(62));
+        let config_bytes = match self.context.get_state_entry(&config_address)? {
+            Some(config_bytes) => config_bytes,
+            None => return Ok(None)
+        };
+        let config = match BlockInfoConfig::parse_from_bytes(&config_bytes) {
+            Ok(config) => config,
+            Err(e) => return Err(ContextError::SerializationError(Box::new(e)))
+        };
+
+        let block_address = format!("{}00{:062x}", BLOCK_INFO_NAMESPACE, config.get_latest_block());
+        let block_bytes = match self.context.get_state_entry(&block_address)? {
+            Some(block_bytes) => block_bytes,
+            None => return Ok(None)
+        };
+        match BlockInfo::parse_from_bytes(&block_bytes) {
+            Ok(block_info) => Ok(Some(block_info.get_timestamp())),
+            Err(e) => Err(ContextError::SerializationError(Box::new(e)))
+        }
+    }
+
+    pub fn add_receipt_data(&self, data: &[u8]) -> Result<(),ContextError> {
+        self.context.add_receipt_data(data)
+    }
+
     pub fn does_entry_exist(&self, name: &str) -> Result<bool, ContextError> {
         // just call context getstate then check if zero bytes or more
         match self.context.get_state_entry(
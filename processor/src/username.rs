@@ -0,0 +1 @@
+../../client/src/username.rs
\ No newline at end of file